@@ -0,0 +1,134 @@
+//! Animation channels, which let the parameters of an `Sdf` change over time without rebuilding it.
+
+use crate::*;
+
+/// How a keyframe's value changes on the way to the next keyframe.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Interpolation {
+    /// Hold the value until the next keyframe.
+    Step,
+    /// Change the value at a constant rate.
+    Linear,
+    /// Follow a cubic Hermite spline, which leaves this keyframe and arrives at the next one with
+    /// their `slope`s.
+    Hermite,
+}
+
+/// A value that a channel passes through at some time.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Keyframe {
+    /// The time of this keyframe, in seconds.
+    pub time: f32,
+    /// The value of the channel at `time`.
+    pub value: f32,
+    /// The rate of change at `time`, in units per second. Only used by `Interpolation::Hermite`.
+    pub slope: f32,
+    /// How to get from this keyframe to the next one.
+    pub interpolation: Interpolation,
+}
+
+/// A scalar that changes over time.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Channel {
+    /// Interpolate between keyframes, which must be sorted by time.
+    /// The first and last values are held before and after the keyframes.
+    Keyframes(Vec<Keyframe>),
+    /// Grow at a constant rate per second, for things that turn at a constant speed.
+    Linear(f32),
+    /// Oscillate around zero, for things that bob or sway.
+    Sine {
+        /// The largest value.
+        amplitude: f32,
+        /// The number of oscillations per second.
+        frequency: f32,
+        /// The phase at time zero, in radians.
+        phase: f32,
+    },
+}
+
+impl Channel {
+    /// Get the value of this channel at time `t`.
+    #[must_use]
+    pub fn at<S: Scalar>(&self, t: S) -> S {
+        let scalar = S::from_f32;
+        match self {
+            Channel::Keyframes(keyframes) => {
+                let next = keyframes.partition_point(|key| scalar(key.time) <= t);
+                if next == 0 {
+                    return keyframes.first().map_or(S::ZERO, |key| scalar(key.value));
+                }
+                let (a, Some(b)) = (keyframes[next - 1], keyframes.get(next)) else {
+                    return scalar(keyframes[next - 1].value);
+                };
+                let duration = scalar(b.time - a.time);
+                let s = (t - scalar(a.time)) / duration;
+                match a.interpolation {
+                    Interpolation::Step => scalar(a.value),
+                    Interpolation::Linear => scalar(a.value) + s * scalar(b.value - a.value),
+                    Interpolation::Hermite => {
+                        let (s2, s3) = (s * s, s * s * s);
+                        (scalar(2.0) * s3 - scalar(3.0) * s2 + S::ONE) * scalar(a.value)
+                            + (s3 - scalar(2.0) * s2 + s) * duration * scalar(a.slope)
+                            + (scalar(-2.0) * s3 + scalar(3.0) * s2) * scalar(b.value)
+                            + (s3 - s2) * duration * scalar(b.slope)
+                    }
+                }
+            }
+            Channel::Linear(rate) => scalar(*rate) * t,
+            Channel::Sine {
+                amplitude,
+                frequency,
+                phase,
+            } => scalar(*amplitude) * (scalar(2.0 * PI * frequency) * t + scalar(*phase)).sin(),
+        }
+    }
+}
+
+/// A scalar parameter of an `Sdf` node: a constant, plus the value of a channel if it has one.
+/// Plain `f32`s and `Channel`s convert into parameters, so constructors accept either.
+#[derive(Clone, PartialEq)]
+pub struct Param {
+    /// The constant part of the parameter.
+    pub base: f32,
+    /// The animated part of the parameter, if any.
+    pub channel: Option<Channel>,
+}
+
+impl Param {
+    /// Get the value of this parameter at time `t`.
+    #[must_use]
+    pub fn at<S: Scalar>(&self, t: S) -> S {
+        S::from_f32(self.base)
+            + self
+                .channel
+                .as_ref()
+                .map_or(S::ZERO, |channel| channel.at(t))
+    }
+}
+
+impl std::fmt::Debug for Param {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match &self.channel {
+            None => write!(f, "{:?}", self.base),
+            Some(channel) => write!(f, "{:?}+{channel:?}", self.base),
+        }
+    }
+}
+
+impl From<f32> for Param {
+    fn from(base: f32) -> Param {
+        Param {
+            base,
+            channel: None,
+        }
+    }
+}
+
+impl From<Channel> for Param {
+    fn from(channel: Channel) -> Param {
+        Param {
+            base: 0.0,
+            channel: Some(channel),
+        }
+    }
+}
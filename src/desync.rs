@@ -0,0 +1,325 @@
+//! Desync diagnostics: hashing the simulation's state the same way on every machine, per system
+//! and per tick, and dumping it as text, so that when two peers diverge, diffing their dumps
+//! shows which system diverged first, and which field.
+//!
+//! A dump has one `key = value` line per leaf of the state, with dotted keys. The `hashes`
+//! section has the hash of each system after each of the ticks leading up to the desync, and the
+//! `state` section has the whole state at the tick where it was noticed.
+
+use crate::*;
+use std::collections::VecDeque;
+use std::fmt::{Display, Write};
+
+/// The number of recent ticks whose per-system hashes a `DesyncLog` keeps, which must be more
+/// than a peer can get ahead of the other.
+const HISTORY_TICKS: usize = 240;
+
+/// The number of recent checkpoints that a `DesyncLog` keeps dumps of.
+const HISTORY_DUMPS: usize = 8;
+
+/// State that can be hashed by its exact bits, so that the hash is the same on every machine
+/// that has the same state, and dumped as text.
+pub trait StableHash {
+    /// Feed this value into `hasher`, using `field` for each of its parts and `write` for leaves.
+    fn stable_hash(&self, hasher: &mut StableHasher);
+}
+
+/// Hashes state with FNV-1a, both in total and per system, and optionally dumps every leaf as a
+/// `key = value` line.
+#[derive(Default)]
+pub struct StableHasher {
+    bytes: Vec<u8>,
+    key: String,
+    in_system: bool,
+    systems: Vec<(String, u64)>,
+    dump: Option<String>,
+}
+
+impl StableHasher {
+    /// Start hashing.
+    #[must_use]
+    pub fn new() -> StableHasher {
+        StableHasher::default()
+    }
+
+    /// Start hashing, and dumping every leaf.
+    #[must_use]
+    pub fn dumping() -> StableHasher {
+        StableHasher {
+            dump: Some(String::new()),
+            ..StableHasher::default()
+        }
+    }
+
+    /// Hash the state of one of the simulation's systems, which is also hashed on its own.
+    pub fn system<T: StableHash + ?Sized>(&mut self, name: &str, state: &T) {
+        assert!(!self.in_system, "systems can't contain systems");
+        let start = self.bytes.len();
+        self.in_system = true;
+        self.field(name, state);
+        self.in_system = false;
+        self.systems
+            .push((name.to_string(), fnv1a(&self.bytes[start..])));
+    }
+
+    /// Hash a named part of the value that is being hashed.
+    pub fn field<T: StableHash + ?Sized>(&mut self, name: impl Display, value: &T) {
+        self.field_with(name, |hasher| value.stable_hash(hasher));
+    }
+
+    /// Hash a named part of the value that is being hashed, with a function that feeds it in.
+    /// Names that start with `[` are indices, and aren't separated from the key by a dot. The name
+    /// is only formatted when dumping, so `format_args!` makes an index free to hash.
+    pub fn field_with(&mut self, name: impl Display, f: impl FnOnce(&mut StableHasher)) {
+        let len = self.key.len();
+        if self.dump.is_some() {
+            write!(self.key, "{name}").unwrap();
+            if len > 0 && !self.key[len..].starts_with('[') {
+                self.key.insert(len, '.');
+            }
+        }
+        f(self);
+        self.key.truncate(len);
+    }
+
+    /// Hash a leaf, whose exact bits are `bytes`, and which is dumped as `value`. The value is only
+    /// formatted when dumping.
+    pub fn write(&mut self, bytes: &[u8], value: impl Display) {
+        self.bytes.extend_from_slice(bytes);
+        if let Some(dump) = &mut self.dump {
+            writeln!(dump, "{} = {value}", self.key).unwrap();
+        }
+    }
+
+    /// Get the hash of everything.
+    #[must_use]
+    pub fn finish(&self) -> u64 {
+        fnv1a(&self.bytes)
+    }
+
+    /// Get the hash of each system, in order.
+    #[must_use]
+    pub fn systems(&self) -> &[(String, u64)] {
+        &self.systems
+    }
+
+    /// Get the dump, which is empty unless this was constructed with `dumping`.
+    #[must_use]
+    pub fn dump(&self) -> &str {
+        self.dump.as_deref().unwrap_or_default()
+    }
+}
+
+macro_rules! stable_hash_integer {
+    ($($t:ty),*) => {$(
+        impl StableHash for $t {
+            fn stable_hash(&self, hasher: &mut StableHasher) {
+                hasher.write(&self.to_le_bytes(), self);
+            }
+        }
+    )*};
+}
+
+stable_hash_integer!(u8, u16, u32, u64, i8, i16, i32, i64);
+
+/// Hashed as a `u64`, so that it is the same on 32-bit machines.
+impl StableHash for usize {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        hasher.write(&(*self as u64).to_le_bytes(), self);
+    }
+}
+
+impl StableHash for bool {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        hasher.write(&[u8::from(*self)], self);
+    }
+}
+
+/// Hashed by its bits, so that `0.0` and `-0.0` differ, and dumped with them.
+impl StableHash for f32 {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        let bits = self.to_bits();
+        hasher.write(&bits.to_le_bytes(), format_args!("{self:?} ({bits:08x})"));
+    }
+}
+
+impl StableHash for Fixed {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        let bits = self.to_bits();
+        hasher.write(&bits.to_le_bytes(), format_args!("{self:?} ({bits:016x})"));
+    }
+}
+
+impl<S: StableHash> StableHash for Vector2<S> {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        hasher.field("x", &self.x);
+        hasher.field("y", &self.y);
+    }
+}
+
+impl<S: StableHash> StableHash for Vector3<S> {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        hasher.field("x", &self.x);
+        hasher.field("y", &self.y);
+        hasher.field("z", &self.z);
+    }
+}
+
+impl<S: StableHash> StableHash for Vector4<S> {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        hasher.field("x", &self.x);
+        hasher.field("y", &self.y);
+        hasher.field("z", &self.z);
+        hasher.field("w", &self.w);
+    }
+}
+
+impl StableHash for Transform {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        hasher.field("x", &self.x);
+        hasher.field("y", &self.y);
+        hasher.field("z", &self.z);
+        hasher.field("w", &self.w);
+    }
+}
+
+impl StableHash for InputFrame {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        let bits = self.to_bits();
+        hasher.write(&[bits], format_args!("{bits:05b}"));
+    }
+}
+
+impl<T: StableHash, const N: usize> StableHash for [T; N] {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        for (i, x) in self.iter().enumerate() {
+            hasher.field(format_args!("[{i}]"), x);
+        }
+    }
+}
+
+/// Hashes the length first, so that moving an element from one slice to the next changes it.
+impl<T: StableHash> StableHash for [T] {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        hasher.field("len", &self.len());
+        for (i, x) in self.iter().enumerate() {
+            hasher.field(format_args!("[{i}]"), x);
+        }
+    }
+}
+
+impl<T: StableHash> StableHash for Vec<T> {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        self.as_slice().stable_hash(hasher);
+    }
+}
+
+/// The per-system hashes of the last few ticks, and dumps of the last few checkpoints, which are
+/// what a peer needs to explain a desync.
+#[derive(Default)]
+pub struct DesyncLog {
+    hashes: VecDeque<(u64, Vec<(String, u64)>)>,
+    dumps: VecDeque<(u64, String)>,
+}
+
+impl DesyncLog {
+    /// Start an empty log.
+    #[must_use]
+    pub fn new() -> DesyncLog {
+        DesyncLog::default()
+    }
+
+    /// Hash the state of the simulation after `tick` ticks, keeping the hash of each system, and
+    /// a dump if the tick is a checkpoint. Returns its checksum.
+    pub fn record<S: Simulation>(&mut self, tick: u64, simulation: &S) -> u64 {
+        let mut hasher = if is_checkpoint(tick) {
+            StableHasher::dumping()
+        } else {
+            StableHasher::new()
+        };
+        simulation.hash_state(&mut hasher);
+
+        self.hashes.push_back((tick, hasher.systems().to_vec()));
+        if self.hashes.len() > HISTORY_TICKS {
+            self.hashes.pop_front();
+        }
+        if is_checkpoint(tick) {
+            self.dumps.push_back((tick, hasher.dump().to_string()));
+            if self.dumps.len() > HISTORY_DUMPS {
+                self.dumps.pop_front();
+            }
+        }
+        hasher.finish()
+    }
+
+    /// Get a dump of the state after `tick` ticks, which must be a recent checkpoint, with the
+    /// hashes of each system after each tick since the checkpoint before it.
+    #[must_use]
+    pub fn dump(&self, tick: u64) -> Option<String> {
+        let (_, state) = self.dumps.iter().find(|(t, _)| *t == tick)?;
+        let mut out = format!("tick = {tick}\n");
+        let since = tick.saturating_sub(CHECKPOINT_INTERVAL);
+        for (t, systems) in self
+            .hashes
+            .iter()
+            .filter(|(t, _)| (since + 1..=tick).contains(t))
+        {
+            for (name, hash) in systems {
+                writeln!(out, "hashes.{t}.{name} = {hash:016x}").unwrap();
+            }
+        }
+        for line in state.lines() {
+            writeln!(out, "state.{line}").unwrap();
+        }
+        Some(out)
+    }
+}
+
+/// Compare two peers' dumps from `DesyncLog::dump`, and describe the first line that differs in
+/// each section. In the `hashes` section, that is the first system to diverge, and in the `state`
+/// section, it is the first field that differs.
+#[must_use]
+pub fn diff_dumps(a: &str, b: &str) -> Vec<String> {
+    let section = |key: &str| key.split('.').next().unwrap_or_default().to_string();
+    let parse = |dump: &str| -> Vec<(String, String)> {
+        dump.lines()
+            .filter_map(|line| line.split_once(" = "))
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    };
+    let (a, b) = (parse(a), parse(b));
+
+    let mut sections: Vec<String> = vec![];
+    for (key, _) in a.iter().chain(&b) {
+        if !sections.contains(&section(key)) {
+            sections.push(section(key));
+        }
+    }
+
+    let mut report = vec![];
+    for name in sections {
+        let lines = |dump: &[(String, String)]| -> Vec<(String, String)> {
+            dump.iter()
+                .filter(|(key, _)| section(key) == name)
+                .cloned()
+                .collect()
+        };
+        let (a, b) = (lines(&a), lines(&b));
+        for i in 0..a.len().max(b.len()) {
+            let difference = match (a.get(i), b.get(i)) {
+                (Some((key, x)), Some((other, y))) if key == other => {
+                    (x != y).then(|| format!("{key}: {x} != {y}"))
+                }
+                (Some((key, _)), Some((other, _))) => Some(format!("{key} != {other}")),
+                (Some((key, x)), None) => Some(format!("{key} = {x} is only in the first")),
+                (None, Some((key, y))) => Some(format!("{key} = {y} is only in the second")),
+                (None, None) => None,
+            };
+            if let Some(difference) = difference {
+                report.push(difference);
+                break;
+            }
+        }
+    }
+    report
+}
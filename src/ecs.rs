@@ -0,0 +1,197 @@
+//! A small entity-component system for the simulation.
+//!
+//! Components are stored in vectors indexed by entity, and everything is iterated in order of
+//! entity index, never in the order of a hash, so that every peer visits entities in the same
+//! order. Despawned entities' indices are reused in a deterministic order too.
+
+use crate::*;
+
+/// Identifies an entity. The generation tells it apart from earlier entities that had the same
+/// index, so that a stale `Entity` never refers to a newer one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Entity {
+    index: u32,
+    generation: u32,
+}
+
+impl Entity {
+    /// Get the index of this entity, which is unique among the living ones.
+    #[must_use]
+    pub fn index(self) -> usize {
+        self.index as usize
+    }
+
+    /// Get the generation of this entity, which counts the entities that had its index before it.
+    #[must_use]
+    pub fn generation(self) -> u32 {
+        self.generation
+    }
+}
+
+impl StableHash for Entity {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        let bytes = [self.index.to_le_bytes(), self.generation.to_le_bytes()].concat();
+        hasher.write(&bytes, format_args!("{}v{}", self.index, self.generation));
+    }
+}
+
+/// A system, which updates the world once per tick, given the inputs of that tick.
+pub type System<W, I> = fn(&mut W, I);
+
+/// Allocates entities, and keeps track of which ones are alive.
+#[derive(Clone, Debug, Default)]
+pub struct Entities {
+    generations: Vec<u32>,
+    alive: Vec<bool>,
+    free: Vec<u32>, // despawned indices, which are reused last in, first out
+}
+
+impl Entities {
+    /// Start with no entities.
+    #[must_use]
+    pub fn new() -> Entities {
+        Entities::default()
+    }
+
+    /// Allocate a new entity.
+    pub fn spawn(&mut self) -> Entity {
+        let index = self.free.pop().unwrap_or_else(|| {
+            self.generations.push(0);
+            self.alive.push(false);
+            u32::try_from(self.alive.len() - 1).expect("too many entities")
+        });
+        self.alive[index as usize] = true;
+        Entity {
+            index,
+            generation: self.generations[index as usize],
+        }
+    }
+
+    /// Free an entity, so that its index can be reused. Returns false if it was already dead.
+    /// Its components must be removed separately.
+    pub fn despawn(&mut self, entity: Entity) -> bool {
+        if !self.is_alive(entity) {
+            return false;
+        }
+        self.alive[entity.index()] = false;
+        self.generations[entity.index()] += 1;
+        self.free.push(entity.index);
+        true
+    }
+
+    /// Check whether an entity is alive.
+    #[must_use]
+    pub fn is_alive(&self, entity: Entity) -> bool {
+        self.alive.get(entity.index()) == Some(&true)
+            && self.generations[entity.index()] == entity.generation
+    }
+
+    /// Iterate over the living entities, in order of index.
+    pub fn iter(&self) -> impl Iterator<Item = Entity> + '_ {
+        (0..self.alive.len())
+            .filter(|&i| self.alive[i])
+            .map(|i| Entity {
+                index: i as u32,
+                generation: self.generations[i],
+            })
+    }
+}
+
+impl StableHash for Entities {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        hasher.field("generations", &self.generations);
+        hasher.field("alive", &self.alive);
+        hasher.field("free", &self.free);
+    }
+}
+
+/// One kind of component, stored by entity index.
+#[derive(Clone, Debug)]
+pub struct Components<T> {
+    slots: Vec<Option<(u32, T)>>, // the generation of the entity that owns it, and the component
+}
+
+impl<T> Default for Components<T> {
+    fn default() -> Components<T> {
+        Components { slots: vec![] }
+    }
+}
+
+impl<T> Components<T> {
+    /// Start with no components.
+    #[must_use]
+    pub fn new() -> Components<T> {
+        Components::default()
+    }
+
+    /// Give an entity this component, and get the one that it had before, if any.
+    pub fn insert(&mut self, entity: Entity, component: T) -> Option<T> {
+        if self.slots.len() <= entity.index() {
+            self.slots.resize_with(entity.index() + 1, || None);
+        }
+        let old = self.slots[entity.index()].replace((entity.generation, component));
+        old.and_then(|(generation, old)| (generation == entity.generation).then_some(old))
+    }
+
+    /// Take this component away from an entity, and get it, if it had one.
+    pub fn remove(&mut self, entity: Entity) -> Option<T> {
+        let slot = self.slots.get_mut(entity.index())?;
+        if slot.as_ref()?.0 != entity.generation {
+            return None;
+        }
+        slot.take().map(|(_, component)| component)
+    }
+
+    /// Get an entity's component.
+    #[must_use]
+    pub fn get(&self, entity: Entity) -> Option<&T> {
+        match self.slots.get(entity.index())? {
+            Some((generation, component)) if *generation == entity.generation => Some(component),
+            _ => None,
+        }
+    }
+
+    /// Get an entity's component mutably.
+    pub fn get_mut(&mut self, entity: Entity) -> Option<&mut T> {
+        match self.slots.get_mut(entity.index())? {
+            Some((generation, component)) if *generation == entity.generation => Some(component),
+            _ => None,
+        }
+    }
+
+    /// Iterate over the entities that have this component, in order of index.
+    pub fn iter(&self) -> impl Iterator<Item = (Entity, &T)> {
+        self.slots.iter().enumerate().filter_map(|(i, slot)| {
+            let (generation, component) = slot.as_ref()?;
+            let entity = Entity {
+                index: i as u32,
+                generation: *generation,
+            };
+            Some((entity, component))
+        })
+    }
+
+    /// Iterate mutably over the entities that have this component, in order of index.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (Entity, &mut T)> {
+        self.slots.iter_mut().enumerate().filter_map(|(i, slot)| {
+            let (generation, component) = slot.as_mut()?;
+            let entity = Entity {
+                index: i as u32,
+                generation: *generation,
+            };
+            Some((entity, component))
+        })
+    }
+}
+
+impl<T: StableHash> StableHash for Components<T> {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        hasher.field("len", &self.iter().count());
+        for (entity, component) in self.iter() {
+            hasher.field_with(format_args!("[{}]", entity.index), |hasher| {
+                hasher.field("generation", &entity.generation);
+                component.stable_hash(hasher);
+            });
+        }
+    }
+}
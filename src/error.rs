@@ -0,0 +1,102 @@
+//! The errors that the library and the renderer can fail with.
+
+use crate::*;
+
+/// Everything that can go wrong, other than bugs.
+#[derive(Debug)]
+pub enum Error {
+    /// An encoded `Sdf` was invalid.
+    Decode(DecodeError),
+    /// An `Sdf` is deeper than the shader's traversal stack.
+    TooDeep {
+        /// The depth of the `Sdf`, as returned by `Sdf::depth`.
+        depth: usize,
+    },
+    /// The event loop could not be created or failed while running.
+    EventLoop(winit::error::EventLoopError),
+    /// The window could not be created.
+    Window(winit::error::OsError),
+    /// The window's surface could not be created.
+    CreateSurface(wgpu::CreateSurfaceError),
+    /// There is no graphics adapter that can draw to the window.
+    NoAdapter,
+    /// The graphics adapter refused to provide a device.
+    RequestDevice(wgpu::RequestDeviceError),
+    /// The window's surface failed in a way that reconfiguring it can't fix.
+    Surface(wgpu::SurfaceError),
+    /// A recording of the inputs couldn't be written.
+    SaveReplay(std::io::Error),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Error::Decode(e) => write!(f, "invalid sdf: {e}"),
+            Error::TooDeep { depth } => write!(
+                f,
+                "sdf is {depth} nodes deep, but the shader can only traverse {MAX_DEPTH}"
+            ),
+            Error::EventLoop(e) => write!(f, "event loop failed: {e}"),
+            Error::Window(e) => write!(f, "could not create a window: {e}"),
+            Error::CreateSurface(e) => write!(f, "could not create a surface: {e}"),
+            Error::NoAdapter => write!(
+                f,
+                "no graphics adapter found; a GPU with Vulkan, Metal, DX12 or OpenGL drivers is required"
+            ),
+            Error::RequestDevice(e) => write!(f, "could not open the graphics device: {e}"),
+            Error::Surface(e) => write!(f, "surface failed: {e}"),
+            Error::SaveReplay(e) => write!(f, "could not save the replay: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Decode(e) => Some(e),
+            Error::EventLoop(e) => Some(e),
+            Error::Window(e) => Some(e),
+            Error::CreateSurface(e) => Some(e),
+            Error::RequestDevice(e) => Some(e),
+            Error::Surface(e) => Some(e),
+            Error::SaveReplay(e) => Some(e),
+            Error::TooDeep { .. } | Error::NoAdapter => None,
+        }
+    }
+}
+
+impl From<DecodeError> for Error {
+    fn from(e: DecodeError) -> Error {
+        Error::Decode(e)
+    }
+}
+
+impl From<winit::error::EventLoopError> for Error {
+    fn from(e: winit::error::EventLoopError) -> Error {
+        Error::EventLoop(e)
+    }
+}
+
+impl From<winit::error::OsError> for Error {
+    fn from(e: winit::error::OsError) -> Error {
+        Error::Window(e)
+    }
+}
+
+impl From<wgpu::CreateSurfaceError> for Error {
+    fn from(e: wgpu::CreateSurfaceError) -> Error {
+        Error::CreateSurface(e)
+    }
+}
+
+impl From<wgpu::RequestDeviceError> for Error {
+    fn from(e: wgpu::RequestDeviceError) -> Error {
+        Error::RequestDevice(e)
+    }
+}
+
+impl From<wgpu::SurfaceError> for Error {
+    fn from(e: wgpu::SurfaceError) -> Error {
+        Error::Surface(e)
+    }
+}
@@ -0,0 +1,238 @@
+//! Deterministic fixed-point math for the simulation, which must produce bit-identical results on
+//! every machine so that lockstep peers stay in sync. Floats can't promise that, because compilers
+//! may fuse multiply-adds and libm's `sqrt`, `sin` and `cos` vary between platforms.
+//! Everything here is integer arithmetic, and converting to `f32` is only for rendering.
+
+use crate::*;
+use std::ops::*;
+
+/// A Q32.32 fixed-point number: a 64-bit integer that counts 2^-32ths.
+/// Arithmetic saturates at `MIN` and `MAX` instead of overflowing, so far-away distances stay far
+/// away, and dividing by zero saturates too.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[must_use]
+pub struct Fixed(i64);
+
+/// The number of fractional bits in a `Fixed`.
+const FRAC_BITS: u32 = 32;
+
+impl Fixed {
+    /// Zero.
+    pub const ZERO: Fixed = Fixed(0);
+    /// One.
+    pub const ONE: Fixed = Fixed(1 << FRAC_BITS);
+    /// The smallest positive value.
+    pub const EPSILON: Fixed = Fixed(1);
+    /// The largest value, which stands in for infinity.
+    pub const MAX: Fixed = Fixed(i64::MAX);
+    /// The smallest value, which is `-MAX` so that negating it is exact.
+    pub const MIN: Fixed = Fixed(-i64::MAX);
+    /// Pi, rounded to the nearest representable value.
+    pub const PI: Fixed = Fixed(13_493_037_705);
+    /// Half of pi, rounded to the nearest representable value.
+    pub const FRAC_PI_2: Fixed = Fixed(6_746_518_852);
+    /// Two pi, rounded to the nearest representable value.
+    pub const TAU: Fixed = Fixed(26_986_075_409);
+
+    /// Construct a value from its raw bits, which count 2^-32ths.
+    pub const fn from_bits(bits: i64) -> Fixed {
+        Fixed(bits)
+    }
+
+    /// Get the raw bits of this value, which count 2^-32ths.
+    #[must_use]
+    pub const fn to_bits(self) -> i64 {
+        self.0
+    }
+
+    /// Construct a value from an integer, which is always exact.
+    pub const fn from_int(x: i32) -> Fixed {
+        Fixed((x as i64) << FRAC_BITS)
+    }
+
+    /// Convert a 128-bit result back into a `Fixed`, saturating.
+    fn saturate(bits: i128) -> Fixed {
+        Fixed(bits.clamp(-i128::from(i64::MAX), i128::from(i64::MAX)) as i64)
+    }
+
+    /// Compute `sin(x)` for `x` in `[-pi/2, pi/2]`, with its Taylor series up to `x^13`,
+    /// which is accurate to a few units in the last place.
+    fn sin_near_zero(x: Fixed) -> Fixed {
+        let x2 = x * x;
+        let mut sum = Fixed::ONE;
+        for k in (1..=6).rev() {
+            sum = Fixed::ONE - sum * x2 / Fixed::from_int((2 * k) * (2 * k + 1));
+        }
+        x * sum
+    }
+}
+
+impl Add for Fixed {
+    type Output = Fixed;
+    fn add(self, other: Fixed) -> Fixed {
+        Fixed::saturate(i128::from(self.0) + i128::from(other.0))
+    }
+}
+
+impl Sub for Fixed {
+    type Output = Fixed;
+    fn sub(self, other: Fixed) -> Fixed {
+        Fixed::saturate(i128::from(self.0) - i128::from(other.0))
+    }
+}
+
+impl Neg for Fixed {
+    type Output = Fixed;
+    fn neg(self) -> Fixed {
+        Fixed::saturate(-i128::from(self.0))
+    }
+}
+
+/// Rounds to the nearest value, with halves rounded up.
+impl Mul for Fixed {
+    type Output = Fixed;
+    fn mul(self, other: Fixed) -> Fixed {
+        let product = i128::from(self.0) * i128::from(other.0);
+        Fixed::saturate((product + (1 << (FRAC_BITS - 1))) >> FRAC_BITS)
+    }
+}
+
+/// Rounds toward zero.
+impl Div for Fixed {
+    type Output = Fixed;
+    fn div(self, other: Fixed) -> Fixed {
+        if other.0 == 0 {
+            return match self.0.signum() {
+                1 => Fixed::MAX,
+                -1 => Fixed::MIN,
+                _ => Fixed::ZERO,
+            };
+        }
+        Fixed::saturate((i128::from(self.0) << FRAC_BITS) / i128::from(other.0))
+    }
+}
+
+impl AddAssign for Fixed {
+    fn add_assign(&mut self, other: Fixed) {
+        *self = *self + other;
+    }
+}
+
+impl SubAssign for Fixed {
+    fn sub_assign(&mut self, other: Fixed) {
+        *self = *self - other;
+    }
+}
+
+impl MulAssign for Fixed {
+    fn mul_assign(&mut self, other: Fixed) {
+        *self = *self * other;
+    }
+}
+
+impl DivAssign for Fixed {
+    fn div_assign(&mut self, other: Fixed) {
+        *self = *self / other;
+    }
+}
+
+impl Scalar for Fixed {
+    const ZERO: Fixed = Fixed::ZERO;
+    const ONE: Fixed = Fixed::ONE;
+    const INFINITY: Fixed = Fixed::MAX;
+    const NEG_INFINITY: Fixed = Fixed::MIN;
+
+    /// Infinities saturate, and NaN becomes zero.
+    fn from_f32(x: f32) -> Fixed {
+        // multiplying by a power of two is exact, and float-to-int casts saturate
+        let bits = (f64::from(x) * f64::from(1u32 << 16) * f64::from(1u32 << 16)).round();
+        Ord::max(Fixed(bits as i64), Fixed::MIN)
+    }
+
+    fn to_f32(self) -> f32 {
+        (self.0 as f64 / f64::from(1u32 << 16) / f64::from(1u32 << 16)) as f32
+    }
+
+    fn floor_to_i32(self) -> i32 {
+        (self.0 >> FRAC_BITS).clamp(i32::MIN.into(), i32::MAX.into()) as i32
+    }
+
+    /// Negative values have a square root of zero.
+    fn sqrt(self) -> Fixed {
+        if self.0 <= 0 {
+            return Fixed::ZERO;
+        }
+        // the square root of a 2^-64ths count is a 2^-32ths count
+        let root = ((self.0 as u128) << FRAC_BITS).isqrt();
+        Fixed(root as i64)
+    }
+
+    fn sin_cos(self) -> (Fixed, Fixed) {
+        // reduce to [-pi, pi), and then to [-pi/2, pi/2] by reflecting about the peaks
+        let sin = |x: Fixed| {
+            let mut x = Fixed(x.0.rem_euclid(Fixed::TAU.0));
+            if x >= Fixed::PI {
+                x -= Fixed::TAU;
+            }
+            if x > Fixed::FRAC_PI_2 {
+                x = Fixed::PI - x;
+            } else if x < -Fixed::FRAC_PI_2 {
+                x = -Fixed::PI - x;
+            }
+            Fixed::sin_near_zero(x)
+        };
+        let quarter_turn = Fixed(
+            self.0
+                .wrapping_add(Fixed::FRAC_PI_2.0)
+                .rem_euclid(Fixed::TAU.0),
+        );
+        (sin(self), sin(quarter_turn))
+    }
+
+    fn abs(self) -> Fixed {
+        Fixed(self.0.saturating_abs())
+    }
+
+    fn floor(self) -> Fixed {
+        Fixed(self.0 & !(Fixed::ONE.0 - 1))
+    }
+
+    fn round(self) -> Fixed {
+        let half = Fixed(1 << (FRAC_BITS - 1));
+        if self < Fixed::ZERO {
+            -(-self).round()
+        } else {
+            (self + half).floor()
+        }
+    }
+}
+
+impl std::fmt::Debug for Fixed {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{:?}", self.to_f32())
+    }
+}
+
+/// A `Vector3` of `Fixed`s, which is what the simulation uses.
+pub type FixedVec3 = Vector3<Fixed>;
+
+impl Mul<Vector2<Fixed>> for Fixed {
+    type Output = Vector2<Fixed>;
+    fn mul(self, other: Vector2<Fixed>) -> Vector2<Fixed> {
+        other * self
+    }
+}
+
+impl Mul<Vector3<Fixed>> for Fixed {
+    type Output = Vector3<Fixed>;
+    fn mul(self, other: Vector3<Fixed>) -> Vector3<Fixed> {
+        other * self
+    }
+}
+
+impl Mul<Vector4<Fixed>> for Fixed {
+    type Output = Vector4<Fixed>;
+    fn mul(self, other: Vector4<Fixed>) -> Vector4<Fixed> {
+        other * self
+    }
+}
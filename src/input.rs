@@ -0,0 +1,83 @@
+//! Abstract player input, which the simulation sees instead of window events, so that it can be
+//! recorded, replayed and sent to other players.
+
+use winit::keyboard::KeyCode;
+use winit_input_helper::WinitInputHelper;
+
+/// A button that a player can hold.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Button {
+    /// The left arrow or A.
+    Left,
+    /// The right arrow or D.
+    Right,
+    /// The up arrow or W.
+    Up,
+    /// The down arrow or S.
+    Down,
+    /// Space or Enter.
+    Action,
+}
+
+impl Button {
+    /// Every button, in the order of their bits in an `InputFrame`.
+    pub const ALL: [Button; 5] = [
+        Button::Left,
+        Button::Right,
+        Button::Up,
+        Button::Down,
+        Button::Action,
+    ];
+
+    /// Get the keys that hold this button.
+    fn keys(self) -> [KeyCode; 2] {
+        match self {
+            Button::Left => [KeyCode::ArrowLeft, KeyCode::KeyA],
+            Button::Right => [KeyCode::ArrowRight, KeyCode::KeyD],
+            Button::Up => [KeyCode::ArrowUp, KeyCode::KeyW],
+            Button::Down => [KeyCode::ArrowDown, KeyCode::KeyS],
+            Button::Action => [KeyCode::Space, KeyCode::Enter],
+        }
+    }
+
+    /// Get the bit of this button in an `InputFrame`.
+    fn bit(self) -> u8 {
+        1 << Button::ALL.iter().position(|b| *b == self).unwrap()
+    }
+}
+
+/// The buttons that a player holds during one tick.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct InputFrame(u8);
+
+impl InputFrame {
+    /// Read the buttons that are held on the keyboard.
+    #[must_use]
+    pub fn from_keyboard(input: &WinitInputHelper) -> InputFrame {
+        let mut frame = InputFrame::default();
+        for button in Button::ALL {
+            if button.keys().into_iter().any(|key| input.key_held(key)) {
+                frame.0 |= button.bit();
+            }
+        }
+        frame
+    }
+
+    /// Check whether a button is held.
+    #[must_use]
+    pub fn held(self, button: Button) -> bool {
+        self.0 & button.bit() != 0
+    }
+
+    /// Get the bits of this frame, with one bit per button in the order of `Button::ALL`.
+    #[must_use]
+    pub fn to_bits(self) -> u8 {
+        self.0
+    }
+
+    /// Construct a frame from its bits, if they are all for known buttons.
+    #[must_use]
+    pub fn from_bits(bits: u8) -> Option<InputFrame> {
+        (bits >> Button::ALL.len() == 0).then_some(InputFrame(bits))
+    }
+}
@@ -8,13 +8,29 @@
 #![allow(clippy::too_many_lines)]
 #![allow(clippy::cast_precision_loss)]
 #![allow(clippy::cast_possible_truncation)]
+#![allow(clippy::cast_sign_loss)]
 
+mod animation;
+mod desync;
+mod ecs;
+mod error;
+mod fixed;
+mod input;
 mod math;
+mod net;
+mod physics;
+mod replay;
+mod rollback;
 mod sdf;
+mod simulation;
+mod world;
 
 pub use std::collections::HashMap;
 pub use wgpu::Color;
-pub use {math::*, sdf::*};
+pub use {
+    animation::*, desync::*, ecs::*, error::*, fixed::*, input::*, math::*, net::*, physics::*,
+    replay::*, rollback::*, sdf::*, simulation::*, world::*,
+};
 
 #[derive(Clone, Copy)]
 #[repr(C)]
@@ -23,6 +39,98 @@ struct Camera {
     dir: Vec3,
 }
 
+impl Interpolate for Camera {
+    fn interpolate(&self, other: &Camera, t: f32) -> Camera {
+        Camera {
+            pos: self.pos.interpolate(&other.pos, t),
+            dir: self.dir.interpolate(&other.dir, t),
+        }
+    }
+}
+
+/// The camera's orbit around the scene, at a tenth of a turn per second.
+#[derive(Clone, Default)]
+struct Orbit {
+    angle: Fixed, // in radians, from 0 to 2pi
+}
+
+impl Orbit {
+    /// The distance from the plane of the orbit to the scene.
+    const DISTANCE: f32 = 5.0;
+
+    /// Move the camera along the orbit by one tick.
+    fn step(&mut self) {
+        let speed = Fixed::ONE / Fixed::from_int(10);
+        self.angle += Fixed::TAU * speed * tick_seconds();
+        self.angle = Fixed::from_bits(self.angle.to_bits().rem_euclid(Fixed::TAU.to_bits()));
+    }
+
+    /// Get the camera at this point of the orbit.
+    fn camera(&self) -> Camera {
+        let (sin, cos) = self.angle.sin_cos();
+        Camera {
+            pos: Vec3 {
+                x: cos.to_f32(),
+                y: sin.to_f32(),
+                z: -Orbit::DISTANCE,
+            },
+            dir: Z,
+        }
+    }
+}
+
+impl StableHash for Orbit {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        hasher.field("angle", &self.angle);
+    }
+}
+
+/// The demo's simulation, in which the local player sails the first ship and the camera orbits
+/// the scene. The other ship's player never presses anything.
+#[derive(Clone, Default)]
+struct Demo {
+    orbit: Orbit,
+    world: World,
+}
+
+/// What the demo renders: the camera, and the entities.
+#[derive(Clone)]
+struct DemoFrame {
+    camera: Camera,
+    world: WorldFrame,
+}
+
+impl Interpolate for DemoFrame {
+    fn interpolate(&self, other: &DemoFrame, t: f32) -> DemoFrame {
+        DemoFrame {
+            camera: self.camera.interpolate(&other.camera, t),
+            world: self.world.interpolate(&other.world, t),
+        }
+    }
+}
+
+impl Simulation for Demo {
+    type Input = InputFrame;
+    type RenderState = DemoFrame;
+
+    fn step(&mut self, input: &InputFrame) {
+        self.orbit.step();
+        self.world.step(&[*input, InputFrame::default()]);
+    }
+
+    fn render_state(&self) -> DemoFrame {
+        DemoFrame {
+            camera: self.orbit.camera(),
+            world: self.world.render_state(),
+        }
+    }
+
+    fn hash_state(&self, hasher: &mut StableHasher) {
+        hasher.system("orbit", &self.orbit);
+        self.world.hash_state(hasher);
+    }
+}
+
 #[repr(C)]
 struct GpuUniforms {
     window_width: f32,
@@ -32,25 +140,500 @@ struct GpuUniforms {
     min_dist: f32,
     max_dist: f32,
     max_iter: u32,
+    lipschitz: f32,
 
     camera: Camera,
 }
 
 unsafe fn to_byte_slice<T>(x: &T, size: usize) -> &[u8] {
-    std::slice::from_raw_parts((x as *const T).cast::<u8>(), size)
+    std::slice::from_raw_parts(std::ptr::from_ref(x).cast::<u8>(), size)
+}
+
+/// View a slice of plain data, without any padding bytes, as bytes.
+unsafe fn slice_to_bytes<T>(xs: &[T]) -> &[u8] {
+    std::slice::from_raw_parts(xs.as_ptr().cast::<u8>(), std::mem::size_of_val(xs))
+}
+
+/// A storage buffer, along with a copy of the data that was last uploaded to it.
+struct StorageBuffer<T> {
+    buffer: wgpu::Buffer,
+    data: Vec<T>,
+}
+
+impl<T> StorageBuffer<T> {
+    fn new(device: &wgpu::Device, queue: &wgpu::Queue, data: Vec<T>) -> StorageBuffer<T> {
+        let bytes = unsafe { slice_to_bytes(&data) };
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: bytes.len() as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        queue.write_buffer(&buffer, 0, bytes);
+        StorageBuffer { buffer, data }
+    }
+
+    /// Upload the range of elements that differ from the last upload, which must be the same length.
+    fn update(&mut self, queue: &wgpu::Queue, data: Vec<T>) {
+        let size = std::mem::size_of::<T>();
+        let old = unsafe { slice_to_bytes(&self.data) }.chunks(size);
+        let new = unsafe { slice_to_bytes(&data) }.chunks(size);
+        let changed = |(a, b): (&[u8], &[u8])| a != b;
+        if let Some(first) = old.clone().zip(new.clone()).position(changed) {
+            let last = old.zip(new).rposition(changed).unwrap();
+            let bytes = unsafe { slice_to_bytes(&data[first..=last]) };
+            queue.write_buffer(&self.buffer, (first * size) as u64, bytes);
+        }
+        self.data = data;
+    }
+}
+
+/// The GPU buffers that hold a scene, which are kept between frames so that only the parts of
+/// the scene that change, like animated or edited nodes, are uploaded again.
+struct SceneBuffers {
+    distances: StorageBuffer<GpuDistance>,
+    materials: StorageBuffer<GpuMaterial>,
+    parameters: StorageBuffer<GpuParameter>,
+    bind_group: wgpu::BindGroup,
+}
+
+impl SceneBuffers {
+    fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        layout: &wgpu::BindGroupLayout,
+        uniforms: &wgpu::Buffer,
+        (distances, materials, parameters): GpuRepr,
+    ) -> SceneBuffers {
+        let distances = StorageBuffer::new(device, queue, distances);
+        let materials = StorageBuffer::new(device, queue, materials);
+        let parameters = StorageBuffer::new(device, queue, parameters);
+        let entry = |binding, buffer| wgpu::BindGroupEntry {
+            binding,
+            resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                buffer,
+                offset: 0,
+                size: None,
+            }),
+        };
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout,
+            entries: &[
+                entry(0, uniforms),
+                entry(1, &distances.buffer),
+                entry(2, &materials.buffer),
+                entry(3, &parameters.buffer),
+            ],
+        });
+        SceneBuffers {
+            distances,
+            materials,
+            parameters,
+            bind_group,
+        }
+    }
+
+    /// Check whether a new version of the scene has the same layout, so that it can be uploaded
+    /// into these buffers.
+    fn fits(&self, (distances, materials, parameters): &GpuRepr) -> bool {
+        distances.len() == self.distances.data.len()
+            && materials.len() == self.materials.data.len()
+            && parameters.len() == self.parameters.data.len()
+    }
+
+    fn update(&mut self, queue: &wgpu::Queue, (distances, materials, parameters): GpuRepr) {
+        self.distances.update(queue, distances);
+        self.materials.update(queue, materials);
+        self.parameters.update(queue, parameters);
+    }
+}
+
+/// The scene that the demo renders.
+fn demo_scene() -> Sdf {
+    Sdf::sphere(1.0, Material::Flat(Color::RED))
+        .union(Sdf::sphere(1.0, Material::Flat(Color::GREEN)).translate(X))
+}
+
+/// A scene that uses every kind of node, for checking that distances are deterministic.
+fn determinism_scene() -> Sdf {
+    let material = || Material::Flat(Color::WHITE);
+    let waves = [Wave {
+        direction: X,
+        amplitude: 0.2,
+        wavelength: 3.0,
+        speed: 1.0,
+    }];
+    Sdf::union_all([
+        Sdf::sphere(1.0, material()).scale(1.5).translate(Y),
+        Sdf::plane(Y, -1.0, material()).displace(Noise::Gradient(3), 0.1, 2.0, 3),
+        Sdf::sphere(0.3, material())
+            .repeat_limited(X * 2.0, [3, 1, 1])
+            .twist(Y, 0.3),
+        Sdf::sphere(0.5, material())
+            .shell(0.1)
+            .mirror(X, 0.5)
+            .rotate(Z, Channel::Linear(1.0)),
+        Sdf::ocean(&waves, material()).translate(Y * -3.0),
+        Sdf::sphere(0.5, material())
+            .taper(Y, 0.2)
+            .elongate(X)
+            .symmetric([true, false, true])
+            .offset(Channel::Sine {
+                amplitude: 0.1,
+                frequency: 0.5,
+                phase: 0.0,
+            }),
+    ])
+}
+
+/// What `determinism_hash` returns on every machine. `lockstep determinism` prints the new value
+/// when a change to the fixed-point math is meant to change it.
+const DETERMINISM_HASH: u64 = 0x02ce_c441_857a_012b;
+
+/// Hash the fixed-point distances to `determinism_scene` at pseudorandom points and times. The
+/// inputs come from an integer generator, so every machine should get `DETERMINISM_HASH`.
+#[allow(clippy::cast_possible_wrap)] // random bits are reinterpreted as signed
+fn determinism_hash() -> u64 {
+    let scene = determinism_scene();
+    let mut rng = Rng::new(0);
+    // uniform in [-8, 8)
+    let mut coordinate = || Fixed::from_bits(rng.next_u64() as i64 >> 28);
+    let mut bytes = vec![];
+    for _ in 0..10_000 {
+        let p = FixedVec3 {
+            x: coordinate(),
+            y: coordinate(),
+            z: coordinate(),
+        };
+        let t = coordinate().abs();
+        bytes.extend(scene.distance_at_time(p, t).to_bits().to_le_bytes());
+    }
+    fnv1a(&bytes)
+}
+
+/// A player that presses random buttons, for the harnesses.
+struct Bot {
+    rng: Rng,
+    input: InputFrame,
+}
+
+impl Bot {
+    fn new(seed: u64) -> Bot {
+        Bot {
+            rng: Rng::new(seed),
+            input: InputFrame::default(),
+        }
+    }
+
+    /// Get the input for the next tick. Each combination of buttons is held for a while, like a
+    /// player would.
+    fn next_input(&mut self) -> InputFrame {
+        if self.rng.next_u64().is_multiple_of(10) {
+            let bits = self.rng.next_u64() % (1 << Button::ALL.len());
+            self.input = InputFrame::from_bits(bits as u8).unwrap();
+        }
+        self.input
+    }
+}
+
+/// Connect two UDP sockets on localhost to each other, through links that drop a fifth of the
+/// packets and delay the rest.
+fn loopback_links() -> Result<Vec<LossyLink<std::net::UdpSocket>>, NetError> {
+    let socket = || {
+        let socket = std::net::UdpSocket::bind("127.0.0.1:0")?;
+        socket.set_nonblocking(true)?;
+        Ok::<_, std::io::Error>(socket)
+    };
+    let sockets = [socket()?, socket()?];
+    sockets[0].connect(sockets[1].local_addr()?)?;
+    sockets[1].connect(sockets[0].local_addr()?)?;
+    Ok(sockets
+        .into_iter()
+        .enumerate()
+        .map(|(player, socket)| {
+            LossyLink::new(
+                socket,
+                Fixed::ONE / Fixed::from_int(5),
+                std::time::Duration::from_millis(40),
+                std::time::Duration::from_millis(30),
+                player as u64,
+            )
+        })
+        .collect())
+}
+
+/// Play both sides of a lockstep session over `loopback_links`, with random inputs, until both
+/// have run `ticks` ticks. Returns a summary of how it went.
+fn loopback(ticks: u64) -> Result<String, String> {
+    let mut peers: Vec<_> = loopback_links()
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .enumerate()
+        .map(|(player, link)| {
+            let session = Session::new(link, player, 4);
+            let game = FixedLoop::new(World::new());
+            (
+                session,
+                game,
+                Bot::new(player as u64),
+                None,
+                DesyncLog::new(),
+            )
+        })
+        .collect();
+
+    let mut run = || -> Result<(), NetError> {
+        let mut last_frame = std::time::Instant::now();
+        while peers.iter().any(|(session, ..)| session.tick() < ticks) {
+            std::thread::sleep(std::time::Duration::from_millis(1));
+            let delta = last_frame.elapsed();
+            last_frame = std::time::Instant::now();
+
+            for (session, game, bot, pending, log) in &mut peers {
+                for _ in 0..game.advance(delta) {
+                    // a stalled session drops the input, so it is kept for the next try
+                    let input = *pending.get_or_insert_with(|| bot.next_input());
+                    if session.add_local_input(input) {
+                        *pending = None;
+                    }
+                }
+                while let Some(inputs) = session.next_inputs() {
+                    game.step(&inputs);
+                    let checksum = log.record(session.tick(), game.simulation());
+                    session.report_checksum(session.tick(), checksum)?;
+                }
+                session.poll()?;
+            }
+        }
+        Ok(())
+    };
+    if let Err(e) = run() {
+        return Err(explain_desync(&e, peers.iter().map(|(.., log)| log)));
+    }
+
+    let (sent, dropped, stalls) =
+        peers
+            .iter()
+            .fold((0, 0, 0), |(sent, dropped, stalls), (session, ..)| {
+                let link = session.transport();
+                (
+                    sent + link.sent,
+                    dropped + link.dropped,
+                    stalls + session.stalls(),
+                )
+            });
+    Ok(format!(
+        "{ticks} ticks in sync, with {dropped} of {sent} packets dropped and {stalls} stalls"
+    ))
+}
+
+/// Play both sides of a rollback session over `loopback_links`, like `loopback`.
+fn loopback_rollback(ticks: u64) -> Result<String, String> {
+    let mut peers: Vec<_> = loopback_links()
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .enumerate()
+        .map(|(player, link)| {
+            let rollback = Rollback::new(link, player, 1, 8, World::new());
+            (rollback, Clock::default(), Bot::new(player as u64), None)
+        })
+        .collect();
+
+    let mut run = || -> Result<(), NetError> {
+        let mut last_frame = std::time::Instant::now();
+        while peers
+            .iter()
+            .any(|(rollback, ..)| rollback.session().tick() < ticks)
+        {
+            std::thread::sleep(std::time::Duration::from_millis(1));
+            let delta = last_frame.elapsed();
+            last_frame = std::time::Instant::now();
+
+            for (rollback, clock, bot, pending) in &mut peers {
+                for _ in 0..clock.advance(delta) {
+                    // a stalled session drops the input, so it is kept for the next try
+                    let input = *pending.get_or_insert_with(|| bot.next_input());
+                    if rollback.add_local_input(input) {
+                        *pending = None;
+                    }
+                }
+                rollback.poll()?;
+            }
+        }
+        Ok(())
+    };
+    if let Err(e) = run() {
+        let logs = peers.iter().map(|(rollback, ..)| rollback.desync_log());
+        return Err(explain_desync(&e, logs));
+    }
+
+    let (sent, dropped, rollbacks, resimulated) = peers.iter().fold(
+        (0, 0, 0, 0),
+        |(sent, dropped, rollbacks, resimulated), (rollback, ..)| {
+            let link = rollback.session().transport();
+            (
+                sent + link.sent,
+                dropped + link.dropped,
+                rollbacks + rollback.rollbacks(),
+                resimulated + rollback.resimulated(),
+            )
+        },
+    );
+    Ok(format!(
+        "{ticks} ticks in sync, with {dropped} of {sent} packets dropped and {rollbacks} \
+         rollbacks that re-simulated {resimulated} ticks"
+    ))
+}
+
+/// Describe why a loopback harness failed. If the peers diverged, write each one's dump of the
+/// checkpoint where it was noticed to `desync-<player>.txt`, for `lockstep diff` to compare.
+fn explain_desync<'a>(error: &NetError, logs: impl Iterator<Item = &'a DesyncLog>) -> String {
+    let NetError::Desync(divergence) = error else {
+        return error.to_string();
+    };
+    let mut paths = vec![];
+    for (player, log) in logs.enumerate() {
+        if let Some(dump) = log.dump(divergence.tick) {
+            let path = format!("desync-{player}.txt");
+            if let Err(e) = std::fs::write(&path, dump) {
+                return format!("{error}, and {path}: {e}");
+            }
+            paths.push(path);
+        }
+    }
+    format!(
+        "{error}\nwrote {}, which `lockstep diff` compares",
+        paths.join(" and ")
+    )
+}
+
+/// Step the loopback harness's simulation with random inputs for `ticks` ticks, rolling back
+/// `distance` ticks after every tick.
+fn synctest(ticks: u64, distance: usize) -> Result<(), Divergence> {
+    let mut test = SyncTest::new(World::new(), distance);
+    let mut bots = [Bot::new(0), Bot::new(1)];
+    while test.tick() < ticks {
+        test.step(&bots.each_mut().map(Bot::next_input))?;
+    }
+    Ok(())
+}
+
+const USAGE: &str = "\
+usage: lockstep                        run the demo
+       lockstep export <scene>         write the demo scene to a file
+       lockstep dot <scene>            print a scene file as a Graphviz DOT graph
+       lockstep tree <scene>           print a scene file as an indented tree
+       lockstep determinism            print a hash of fixed-point distances at random points,
+                                       to update `DETERMINISM_HASH` with
+       lockstep record <replay>        run the demo, and record its inputs to a file
+       lockstep replay <replay>        rerun a recording without a window, and check that
+                                       every tick matches
+       lockstep loopback [ticks]       play both sides of a lockstep session over localhost,
+                                       through a link that drops and delays packets
+       lockstep rollback [ticks]       the same, with a rollback session
+       lockstep synctest [ticks]       run the simulation with random inputs, rolling back
+                                       and re-simulating after every tick to check that it
+                                       is deterministic
+       lockstep diff <dump> <dump>     compare two peers' desync dumps, and print the first
+                                       system and field that differ";
+
+/// Run a command-line subcommand, exiting the process if it fails.
+fn command(args: &[String]) {
+    let fail = |message: String| -> ! {
+        eprintln!("{message}");
+        std::process::exit(1)
+    };
+    let read = |path: &str| {
+        let bytes = std::fs::read(path).unwrap_or_else(|e| fail(format!("{path}: {e}")));
+        Sdf::from_bytes(&bytes).unwrap_or_else(|e| fail(format!("{path}: {e}")))
+    };
+    match args {
+        [command, path] if command == "export" => std::fs::write(path, demo_scene().to_bytes())
+            .unwrap_or_else(|e| fail(format!("{path}: {e}"))),
+        [command, path] if command == "dot" => print!("{}", read(path).to_dot()),
+        [command, path] if command == "tree" => print!("{}", read(path)),
+        [command, path] if command == "replay" => {
+            let bytes = std::fs::read(path).unwrap_or_else(|e| fail(format!("{path}: {e}")));
+            let replay =
+                Replay::from_bytes(&bytes).unwrap_or_else(|e| fail(format!("{path}: {e}")));
+            if replay.scene_hash != demo_scene().content_hash() {
+                fail(format!("{path}: recorded with a different scene"));
+            }
+            match replay.verify(&mut Demo::default()) {
+                Ok(()) => println!("{} ticks match", replay.ticks.len()),
+                Err(divergence) => fail(format!("{path}: {divergence}")),
+            }
+        }
+        [command, rest @ ..]
+            if ["loopback", "rollback", "synctest"].contains(&command.as_str()) =>
+        {
+            let ticks = match rest {
+                [] => 300,
+                [ticks] => ticks
+                    .parse()
+                    .unwrap_or_else(|e| fail(format!("{ticks}: {e}"))),
+                _ => fail(USAGE.to_string()),
+            };
+            let summary = match command.as_str() {
+                "loopback" => loopback(ticks),
+                "rollback" => loopback_rollback(ticks),
+                _ => synctest(ticks, 8)
+                    .map(|()| format!("{ticks} ticks match after rolling back 8 ticks every tick"))
+                    .map_err(|e| e.to_string()),
+            };
+            println!("{}", summary.unwrap_or_else(|e| fail(e)));
+        }
+        [command, a, b] if command == "diff" => {
+            let read = |path: &str| {
+                std::fs::read_to_string(path).unwrap_or_else(|e| fail(format!("{path}: {e}")))
+            };
+            let report = diff_dumps(&read(a), &read(b));
+            if report.is_empty() {
+                println!("no differences");
+            }
+            for line in report {
+                println!("{line}");
+            }
+        }
+        [command] if command == "determinism" => {
+            let hash = determinism_hash();
+            println!("{hash:016x}");
+            if hash != DETERMINISM_HASH {
+                eprintln!("this differs from DETERMINISM_HASH, {DETERMINISM_HASH:016x}");
+            }
+        }
+        _ => fail(USAGE.to_string()),
+    }
 }
 
 #[allow(clippy::semicolon_if_nothing_returned)] // pollster macro trips this lint
 #[pollster::main]
 async fn main() {
+    // subcommands
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let record = match &args[..] {
+        [] => None,
+        [command, path] if command == "record" => Some(path.as_str()),
+        _ => return command(&args),
+    };
+
+    if let Err(e) = run(record).await {
+        eprintln!("{e}");
+        std::process::exit(1);
+    }
+}
+
+/// Open a window and render the demo until it is closed, recording its inputs to `record`.
+async fn run(record: Option<&str>) -> Result<(), Error> {
     // winit
-    let event_loop = winit::event_loop::EventLoop::new().unwrap();
-    let window = winit::window::Window::new(&event_loop).unwrap();
+    let event_loop = winit::event_loop::EventLoop::new()?;
+    let window = winit::window::Window::new(&event_loop)?;
     window.set_title("The Shipyard");
 
     // wgpu
     let instance = wgpu::Instance::default();
-    let surface = unsafe { instance.create_surface(&window) }.unwrap();
+    let surface = unsafe { instance.create_surface(&window) }?;
     let adapter = instance
         .request_adapter(&wgpu::RequestAdapterOptions {
             power_preference: wgpu::PowerPreference::default(),
@@ -58,7 +641,7 @@ async fn main() {
             compatible_surface: Some(&surface),
         })
         .await
-        .unwrap();
+        .ok_or(Error::NoAdapter)?;
     let (device, queue) = adapter
         .request_device(
             &wgpu::DeviceDescriptor {
@@ -68,8 +651,7 @@ async fn main() {
             },
             None,
         )
-        .await
-        .unwrap();
+        .await?;
 
     // wgpu (pipeline)
     let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
@@ -109,6 +691,16 @@ async fn main() {
                     min_binding_size: None,
                 },
             },
+            wgpu::BindGroupLayoutEntry {
+                binding: 3,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                count: None,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+            },
         ],
     });
     let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
@@ -139,154 +731,166 @@ async fn main() {
     // shipyard
     let mut input = winit_input_helper::WinitInputHelper::new();
     let mut timer = std::time::Instant::now();
-    let mut seconds = 0.0;
-    let mut camera = Camera {
-        pos: ORIGIN,
-        dir: Z,
+    let mut game = FixedLoop::new(Demo::default());
+    let mut replay = record.map(|_| Replay::new(demo_scene().content_hash()));
+
+    let mut scene = demo_scene();
+    let uniforms_size = std::mem::size_of::<GpuUniforms>();
+    let uniforms_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: None,
+        size: uniforms_size as u64,
+        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+    let mut buffers = SceneBuffers::new(
+        &device,
+        &queue,
+        &bind_group_layout,
+        &uniforms_buffer,
+        scene.to_gpu_repr()?,
+    );
+
+    let size = window.inner_size();
+    let mut config = wgpu::SurfaceConfiguration {
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+        format,
+        width: size.width.max(1),
+        height: size.height.max(1),
+        present_mode: wgpu::PresentMode::Fifo,
+        alpha_mode: wgpu::CompositeAlphaMode::Auto,
+        view_formats: vec![],
     };
+    surface.configure(&device, &config);
 
-    let scene = Sdf::sphere(1.0, Material::Flat(Color::RED))
-        .union(Sdf::sphere(1.0, Material::Flat(Color::GREEN)).translate(X));
+    // the event loop can't return errors, so the first fatal one is kept here
+    let mut error = None;
 
-    event_loop
-        .run(|event, window_target| {
-            if input.update(&event) {
-                if input.close_requested() || input.destroyed() {
-                    window_target.exit();
-                }
+    event_loop.run(|event, window_target| {
+        if input.update(&event) {
+            if input.close_requested() || input.destroyed() {
+                window_target.exit();
+            }
 
-                // update
-                let delta = timer.elapsed();
-                timer = std::time::Instant::now();
-                seconds += delta.as_secs_f32();
-
-                let angle = seconds * (2.0 * PI) * 0.1;
-                camera.pos = Vec3 {
-                    x: angle.cos(),
-                    y: angle.sin(),
-                    z: -5.0,
-                };
-
-                // render
-                let size = window.inner_size();
-                surface.configure(
-                    &device,
-                    &wgpu::SurfaceConfiguration {
-                        usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-                        format,
-                        width: size.width.max(1),
-                        height: size.height.max(1),
-                        present_mode: wgpu::PresentMode::Fifo,
-                        alpha_mode: wgpu::CompositeAlphaMode::Auto,
-                        view_formats: vec![],
-                    },
-                );
-
-                let uniforms_data = GpuUniforms {
-                    window_width: size.width as f32,
-                    window_height: size.height as f32,
-                    seconds,
-                    min_dist: MIN_DIST,
-                    max_dist: MAX_DIST,
-                    max_iter: MAX_ITER as u32,
-                    camera,
-                };
-                let (distances_data, materials_data) = scene.to_gpu_repr();
-
-                let uniforms_size = std::mem::size_of::<GpuUniforms>();
-                let uniforms_buffer = device.create_buffer(&wgpu::BufferDescriptor {
-                    label: None,
-                    size: uniforms_size as u64,
-                    usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-                    mapped_at_creation: false,
-                });
-                queue.write_buffer(&uniforms_buffer, 0, unsafe {
-                    to_byte_slice(&uniforms_data, uniforms_size)
-                });
+            // update
+            let delta = timer.elapsed();
+            timer = std::time::Instant::now();
+            let inputs = InputFrame::from_keyboard(&input);
+            for _ in 0..game.advance(delta) {
+                game.step(&inputs);
+                if let Some(replay) = &mut replay {
+                    replay.record(inputs, game.simulation().checksum());
+                }
+            }
+            let seconds = game.render_seconds();
+            let DemoFrame { camera, world } = game.render_state();
+            scene = demo_scene() | game.simulation().world.scene(&world);
 
-                let distances_size = std::mem::size_of::<GpuDistance>() * distances_data.len();
-                let distances_buffer = device.create_buffer(&wgpu::BufferDescriptor {
-                    label: None,
-                    size: distances_size as u64,
-                    usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
-                    mapped_at_creation: false,
-                });
-                queue.write_buffer(&distances_buffer, 0, unsafe {
-                    to_byte_slice(&distances_data[0], distances_size)
-                });
+            // render
+            let size = window.inner_size();
+            if (size.width.max(1), size.height.max(1)) != (config.width, config.height) {
+                config.width = size.width.max(1);
+                config.height = size.height.max(1);
+                surface.configure(&device, &config);
+            }
 
-                let materials_size = std::mem::size_of::<GpuMaterial>() * materials_data.len();
-                let materials_buffer = device.create_buffer(&wgpu::BufferDescriptor {
-                    label: None,
-                    size: materials_size as u64,
-                    usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
-                    mapped_at_creation: false,
-                });
-                queue.write_buffer(&materials_buffer, 0, unsafe {
-                    to_byte_slice(&materials_data[0], materials_size)
-                });
+            let uniforms_data = GpuUniforms {
+                window_width: size.width as f32,
+                window_height: size.height as f32,
+                seconds,
+                min_dist: MIN_DIST,
+                max_dist: MAX_DIST,
+                max_iter: MAX_ITER as u32,
+                lipschitz: scene.lipschitz_at_time(seconds),
+                camera,
+            };
+            queue.write_buffer(&uniforms_buffer, 0, unsafe {
+                to_byte_slice(&uniforms_data, uniforms_size)
+            });
+            let data = match scene.to_gpu_repr_at_time(seconds) {
+                Ok(data) => data,
+                Err(e) => {
+                    error = Some(e);
+                    window_target.exit();
+                    return;
+                }
+            };
+            if buffers.fits(&data) {
+                buffers.update(&queue, data);
+            } else {
+                buffers =
+                    SceneBuffers::new(&device, &queue, &bind_group_layout, &uniforms_buffer, data);
+            }
 
-                let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            let frame = match surface.get_current_texture() {
+                Ok(frame) => frame,
+                // the surface no longer matches the window, e.g. after a resize
+                Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
+                    surface.configure(&device, &config);
+                    window.request_redraw();
+                    return;
+                }
+                Err(wgpu::SurfaceError::Timeout) => {
+                    window.request_redraw();
+                    return;
+                }
+                Err(e) => {
+                    error = Some(e.into());
+                    window_target.exit();
+                    return;
+                }
+            };
+            let view = frame
+                .texture
+                .create_view(&wgpu::TextureViewDescriptor::default());
+            let mut encoder =
+                device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+            {
+                let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                     label: None,
-                    layout: &bind_group_layout,
-                    entries: &[
-                        wgpu::BindGroupEntry {
-                            binding: 0,
-                            resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
-                                buffer: &uniforms_buffer,
-                                offset: 0,
-                                size: None,
-                            }),
-                        },
-                        wgpu::BindGroupEntry {
-                            binding: 1,
-                            resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
-                                buffer: &distances_buffer,
-                                offset: 0,
-                                size: None,
-                            }),
+                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                        view: &view,
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(Color::BLACK),
+                            store: wgpu::StoreOp::Store,
                         },
-                        wgpu::BindGroupEntry {
-                            binding: 2,
-                            resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
-                                buffer: &materials_buffer,
-                                offset: 0,
-                                size: None,
-                            }),
-                        },
-                    ],
+                    })],
+                    depth_stencil_attachment: None,
+                    timestamp_writes: None,
+                    occlusion_query_set: None,
                 });
+                pass.set_pipeline(&pipeline);
+                pass.set_bind_group(0, &buffers.bind_group, &[]);
+                pass.draw(0..3, 0..1);
+            }
+            queue.submit([encoder.finish()]);
+            frame.present();
 
-                let frame = surface.get_current_texture().unwrap();
-                let view = frame
-                    .texture
-                    .create_view(&wgpu::TextureViewDescriptor::default());
-                let mut encoder =
-                    device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
-                {
-                    let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                        label: None,
-                        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                            view: &view,
-                            resolve_target: None,
-                            ops: wgpu::Operations {
-                                load: wgpu::LoadOp::Clear(Color::BLACK),
-                                store: wgpu::StoreOp::Store,
-                            },
-                        })],
-                        depth_stencil_attachment: None,
-                        timestamp_writes: None,
-                        occlusion_query_set: None,
-                    });
-                    pass.set_pipeline(&pipeline);
-                    pass.set_bind_group(0, &bind_group, &[]);
-                    pass.draw(0..3, 0..1);
-                }
-                queue.submit([encoder.finish()]);
-                frame.present();
+            window.request_redraw();
+        }
+    })?;
+    if let (Some(path), Some(replay)) = (record, replay) {
+        std::fs::write(path, replay.to_bytes()).map_err(Error::SaveReplay)?;
+    }
+    error.map_or(Ok(()), Err)
+}
 
-                window.request_redraw();
-            }
-        })
-        .unwrap();
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn determinism_hash_is_unchanged() {
+        assert_eq!(
+            determinism_hash(),
+            DETERMINISM_HASH,
+            "got {:016x}",
+            determinism_hash()
+        );
+    }
+
+    #[test]
+    fn synctest_finds_no_divergence() {
+        assert_eq!(synctest(150, 8), Ok(()));
+    }
 }
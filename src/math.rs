@@ -2,103 +2,425 @@ use std::ops::*;
 
 pub use std::f32::consts::PI;
 
-/// A vector in R^3.
-#[derive(Clone, Copy, PartialEq)]
-#[repr(align(16))] // WGSL `vec3`s are 16-byte aligned
-#[must_use]
-pub struct Vec3 {
-    /// The x-coordinate.
-    pub x: f32,
-    /// The y-coordinate.
-    pub y: f32,
-    /// The z-coordinate.
-    pub z: f32,
-}
+/// A number that simulation math can be done in: `f32` for rendering, or `Fixed` for results
+/// that must be the same on every machine.
+pub trait Scalar:
+    Copy
+    + PartialOrd
+    + Default
+    + std::fmt::Debug
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Div<Output = Self>
+    + Neg<Output = Self>
+{
+    /// Zero.
+    const ZERO: Self;
+    /// One.
+    const ONE: Self;
+    /// A value larger than every distance, which is infinity if there is one.
+    const INFINITY: Self;
+    /// A value smaller than every distance, which is negative infinity if there is one.
+    const NEG_INFINITY: Self;
+
+    /// Convert an `f32` to the nearest value of this type.
+    fn from_f32(x: f32) -> Self;
+    /// Convert this value to the nearest `f32`.
+    fn to_f32(self) -> f32;
+    /// Round this value toward negative infinity, and convert it to an integer, saturating.
+    fn floor_to_i32(self) -> i32;
+
+    /// Get the square root of this value, which must not be negative.
+    #[must_use]
+    fn sqrt(self) -> Self;
+    /// Get the sine and cosine of this value, in radians.
+    fn sin_cos(self) -> (Self, Self);
+    /// Get the absolute value of this value.
+    #[must_use]
+    fn abs(self) -> Self;
+    /// Round this value toward negative infinity.
+    #[must_use]
+    fn floor(self) -> Self;
+    /// Round this value to the nearest integer, with halves rounded away from zero.
+    #[must_use]
+    fn round(self) -> Self;
+
+    /// Get the sine of this value, in radians.
+    #[must_use]
+    fn sin(self) -> Self {
+        self.sin_cos().0
+    }
 
-impl Vec3 {
-    /// Apply an element-wise unary operation to a vector.
-    /// This had better be inlined.
-    pub fn unary(self, f: impl Fn(f32) -> f32) -> Vec3 {
-        Vec3 {
-            x: f(self.x),
-            y: f(self.y),
-            z: f(self.z),
+    /// Get the cosine of this value, in radians.
+    #[must_use]
+    fn cos(self) -> Self {
+        self.sin_cos().1
+    }
+
+    /// Get the smaller of this value and another one.
+    #[must_use]
+    fn min(self, other: Self) -> Self {
+        if other < self {
+            other
+        } else {
+            self
         }
     }
 
-    /// Apply an element-wise binary operation to two vectors.
-    /// This had better be inlined.
-    pub fn binary(self, other: Vec3, f: impl Fn(f32, f32) -> f32) -> Vec3 {
-        Vec3 {
-            x: f(self.x, other.x),
-            y: f(self.y, other.y),
-            z: f(self.z, other.z),
+    /// Get the larger of this value and another one.
+    #[must_use]
+    fn max(self, other: Self) -> Self {
+        if other > self {
+            other
+        } else {
+            self
         }
     }
 
-    /// Reduce the elements of a vector using a binary operation.
-    /// This had better be inlined.
-    pub fn reduce(self, f: impl Fn(f32, f32) -> f32) -> f32 {
-        f(self.x, f(self.y, self.z))
+    /// Restrict this value to the range from `min` to `max`.
+    #[must_use]
+    fn clamp(self, min: Self, max: Self) -> Self {
+        self.max(min).min(max)
     }
 }
 
-impl Add for Vec3 {
-    type Output = Vec3;
-    fn add(self, other: Vec3) -> Vec3 {
-        self.binary(other, f32::add)
+impl Scalar for f32 {
+    const ZERO: f32 = 0.0;
+    const ONE: f32 = 1.0;
+    const INFINITY: f32 = f32::INFINITY;
+    const NEG_INFINITY: f32 = f32::NEG_INFINITY;
+
+    fn from_f32(x: f32) -> f32 {
+        x
     }
-}
 
-impl Sub for Vec3 {
-    type Output = Vec3;
-    fn sub(self, other: Vec3) -> Vec3 {
-        self.binary(other, f32::sub)
+    fn to_f32(self) -> f32 {
+        self
     }
-}
 
-impl Neg for Vec3 {
-    type Output = Vec3;
-    fn neg(self) -> Vec3 {
-        self.unary(f32::neg)
+    #[allow(clippy::cast_possible_wrap)]
+    fn floor_to_i32(self) -> i32 {
+        f32::floor(self) as i32
+    }
+
+    fn sqrt(self) -> f32 {
+        f32::sqrt(self)
+    }
+
+    fn sin_cos(self) -> (f32, f32) {
+        f32::sin_cos(self)
     }
-}
 
-impl Mul<f32> for Vec3 {
-    type Output = Vec3;
-    fn mul(self, other: f32) -> Vec3 {
-        self.unary(|x| x * other)
+    fn abs(self) -> f32 {
+        f32::abs(self)
+    }
+
+    fn floor(self) -> f32 {
+        f32::floor(self)
+    }
+
+    fn round(self) -> f32 {
+        f32::round(self)
+    }
+
+    // `f32`'s own `min` and `max` ignore NaNs, unlike the default ones
+    fn min(self, other: f32) -> f32 {
+        f32::min(self, other)
+    }
+
+    fn max(self, other: f32) -> f32 {
+        f32::max(self, other)
+    }
+
+    fn clamp(self, min: f32, max: f32) -> f32 {
+        f32::clamp(self, min, max)
     }
 }
 
-impl Div<f32> for Vec3 {
-    type Output = Vec3;
-    fn div(self, other: f32) -> Vec3 {
-        self.unary(|x| x / other)
+/// Define a generic vector type with the given coordinates, and an alias for its `f32` version,
+/// along with the operations that every vector type shares. Everything is built on the `unary`,
+/// `binary` and `reduce` combinators.
+macro_rules! vector {
+    ($(#[$attr:meta])* $name:ident $alias:ident [$n:literal] { $($field:ident),+ }) => {
+        $(#[$attr])*
+        #[derive(Clone, Copy, PartialEq, Eq, Hash, Default)]
+        #[must_use]
+        pub struct $name<S> {
+            $(
+                #[doc = concat!("The ", stringify!($field), "-coordinate.")]
+                pub $field: S,
+            )+
+        }
+
+        #[doc = concat!("A `", stringify!($name), "` of `f32`s, which is what rendering uses.")]
+        pub type $alias = $name<f32>;
+
+        impl<S: Scalar> $name<S> {
+            /// Apply an element-wise unary operation to a vector.
+            /// This had better be inlined.
+            pub fn unary(self, f: impl Fn(S) -> S) -> $name<S> {
+                $name { $($field: f(self.$field)),+ }
+            }
+
+            /// Apply an element-wise binary operation to two vectors.
+            /// This had better be inlined.
+            pub fn binary(self, other: $name<S>, f: impl Fn(S, S) -> S) -> $name<S> {
+                $name { $($field: f(self.$field, other.$field)),+ }
+            }
+
+            /// Reduce the elements of a vector using a binary operation, starting from the last.
+            /// This had better be inlined.
+            pub fn reduce(self, f: impl Fn(S, S) -> S) -> S {
+                <[S; $n]>::from(self)
+                    .into_iter()
+                    .rev()
+                    .reduce(|acc, x| f(x, acc))
+                    .expect("vectors are not empty")
+            }
+
+            /// Convert a vector of `f32`s, element-wise.
+            pub fn from_f32(vec: $name<f32>) -> $name<S> {
+                $name { $($field: S::from_f32(vec.$field)),+ }
+            }
+
+            /// Convert this vector to `f32`s, element-wise.
+            pub fn to_f32(self) -> $name<f32> {
+                $name { $($field: self.$field.to_f32()),+ }
+            }
+        }
+
+        impl<S: Scalar> Add for $name<S> {
+            type Output = $name<S>;
+            fn add(self, other: $name<S>) -> $name<S> {
+                self.binary(other, S::add)
+            }
+        }
+
+        impl<S: Scalar> Sub for $name<S> {
+            type Output = $name<S>;
+            fn sub(self, other: $name<S>) -> $name<S> {
+                self.binary(other, S::sub)
+            }
+        }
+
+        impl<S: Scalar> Neg for $name<S> {
+            type Output = $name<S>;
+            fn neg(self) -> $name<S> {
+                self.unary(S::neg)
+            }
+        }
+
+        impl<S: Scalar> Mul<S> for $name<S> {
+            type Output = $name<S>;
+            fn mul(self, other: S) -> $name<S> {
+                self.unary(|x| x * other)
+            }
+        }
+
+        impl Mul<$name<f32>> for f32 {
+            type Output = $name<f32>;
+            fn mul(self, other: $name<f32>) -> $name<f32> {
+                other * self
+            }
+        }
+
+        impl<S: Scalar> Div<S> for $name<S> {
+            type Output = $name<S>;
+            fn div(self, other: S) -> $name<S> {
+                self.unary(|x| x / other)
+            }
+        }
+
+        /// Element-wise multiplication.
+        impl<S: Scalar> Mul for $name<S> {
+            type Output = $name<S>;
+            fn mul(self, other: $name<S>) -> $name<S> {
+                self.binary(other, S::mul)
+            }
+        }
+
+        /// Element-wise division.
+        impl<S: Scalar> Div for $name<S> {
+            type Output = $name<S>;
+            fn div(self, other: $name<S>) -> $name<S> {
+                self.binary(other, S::div)
+            }
+        }
+
+        impl<S: Scalar> AddAssign for $name<S> {
+            fn add_assign(&mut self, other: $name<S>) {
+                *self = *self + other;
+            }
+        }
+
+        impl<S: Scalar> SubAssign for $name<S> {
+            fn sub_assign(&mut self, other: $name<S>) {
+                *self = *self - other;
+            }
+        }
+
+        impl<S: Scalar> MulAssign<S> for $name<S> {
+            fn mul_assign(&mut self, other: S) {
+                *self = *self * other;
+            }
+        }
+
+        impl<S: Scalar> DivAssign<S> for $name<S> {
+            fn div_assign(&mut self, other: S) {
+                *self = *self / other;
+            }
+        }
+
+        impl<S> Index<usize> for $name<S> {
+            type Output = S;
+            fn index(&self, index: usize) -> &S {
+                [$(&self.$field),+]
+                    .into_iter()
+                    .nth(index)
+                    .unwrap_or_else(|| panic!("index {index} is out of range for {}", stringify!($name)))
+            }
+        }
+
+        impl<S> IndexMut<usize> for $name<S> {
+            fn index_mut(&mut self, index: usize) -> &mut S {
+                [$(&mut self.$field),+]
+                    .into_iter()
+                    .nth(index)
+                    .unwrap_or_else(|| panic!("index {index} is out of range for {}", stringify!($name)))
+            }
+        }
+
+        impl<S> From<[S; $n]> for $name<S> {
+            fn from([$($field),+]: [S; $n]) -> $name<S> {
+                $name { $($field),+ }
+            }
+        }
+
+        impl<S> From<$name<S>> for [S; $n] {
+            fn from(vec: $name<S>) -> [S; $n] {
+                [$(vec.$field),+]
+            }
+        }
+
+        impl<S: Scalar> $name<S> {
+            /// Get the dot product of this vector and another one.
+            pub fn dot(self, other: $name<S>) -> S {
+                self.binary(other, S::mul).reduce(S::add)
+            }
+
+            /// Get the length of this vector.
+            #[must_use]
+            pub fn length(self) -> S {
+                self.dot(self).sqrt()
+            }
+
+            /// Get a vector in the same direction as this one, but with unit length.
+            pub fn normalized(self) -> $name<S> {
+                self / self.length()
+            }
+
+            /// Get the distance between this point and another one.
+            #[must_use]
+            pub fn distance(self, other: $name<S>) -> S {
+                (self - other).length()
+            }
+
+            /// Interpolate linearly from this vector, at `t = 0`, to another one, at `t = 1`.
+            pub fn lerp(self, other: $name<S>, t: S) -> $name<S> {
+                self + (other - self) * t
+            }
+
+            /// Get the element-wise minimum of this vector and another one.
+            pub fn min(self, other: $name<S>) -> $name<S> {
+                self.binary(other, S::min)
+            }
+
+            /// Get the element-wise maximum of this vector and another one.
+            pub fn max(self, other: $name<S>) -> $name<S> {
+                self.binary(other, S::max)
+            }
+
+            /// Get the element-wise absolute value of this vector.
+            pub fn abs(self) -> $name<S> {
+                self.unary(S::abs)
+            }
+        }
+
+        impl<S: std::fmt::Debug> std::fmt::Debug for $name<S> {
+            fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.debug_tuple("")$(.field(&self.$field))+.finish()
+            }
+        }
+    };
+}
+
+vector! {
+    /// A vector in R^2.
+    #[repr(C, align(8))] // WGSL `vec2`s are 8-byte aligned
+    Vector2 Vec2 [2] { x, y }
+}
+
+vector! {
+    /// A vector in R^3.
+    #[repr(C, align(16))] // WGSL `vec3`s are 16-byte aligned
+    Vector3 Vec3 [3] { x, y, z }
+}
+
+vector! {
+    /// A vector in R^4, or a point in R^3 in homogeneous coordinates.
+    #[repr(C, align(16))] // WGSL `vec4`s are 16-byte aligned
+    Vector4 Vec4 [4] { x, y, z, w }
+}
+
+impl<S: Scalar> Vector2<S> {
+    /// Add a z-coordinate to this vector.
+    pub fn extend(self, z: S) -> Vector3<S> {
+        Vector3 {
+            x: self.x,
+            y: self.y,
+            z,
+        }
     }
 }
 
-impl Vec3 {
-    /// Get the dot product of this vector and another one.
-    pub fn dot(self, other: Vec3) -> f32 {
-        self.binary(other, f32::mul).reduce(f32::add)
+impl<S: Scalar> Vector3<S> {
+    /// Get the cross product of this vector and another one, which is perpendicular to both.
+    pub fn cross(self, other: Vector3<S>) -> Vector3<S> {
+        Vector3 {
+            x: self.y * other.z - self.z * other.y,
+            y: self.z * other.x - self.x * other.z,
+            z: self.x * other.y - self.y * other.x,
+        }
     }
 
-    /// Get the length of this vector.
-    #[must_use]
-    pub fn length(self) -> f32 {
-        self.dot(self).sqrt()
+    /// Add a w-coordinate to this vector.
+    pub fn extend(self, w: S) -> Vector4<S> {
+        Vector4 {
+            x: self.x,
+            y: self.y,
+            z: self.z,
+            w,
+        }
     }
 
-    /// Get a vector in the same direction as this one, but with unit length.
-    pub fn normalized(self) -> Vec3 {
-        self / self.length()
+    /// Drop the z-coordinate of this vector.
+    pub fn truncate(self) -> Vector2<S> {
+        Vector2 {
+            x: self.x,
+            y: self.y,
+        }
     }
 }
 
-impl std::fmt::Debug for Vec3 {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "({:?}, {:?}, {:?})", self.x, self.y, self.z)
+impl<S: Scalar> Vector4<S> {
+    /// Drop the w-coordinate of this vector.
+    pub fn truncate(self) -> Vector3<S> {
+        Vector3 {
+            x: self.x,
+            y: self.y,
+            z: self.z,
+        }
     }
 }
 
@@ -129,3 +451,251 @@ pub const Z: Vec3 = Vec3 {
     y: 0.0,
     z: 1.0,
 };
+
+/// A similarity transformation of R^3: a rotation and a uniform scale, followed by a translation.
+/// It is stored as the columns of a 3x4 matrix. Only similarities scale all distances by the same
+/// factor, so only those can be constructed.
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[must_use]
+pub struct Transform {
+    /// The image of `X` under the rotation and scale.
+    pub x: Vec3,
+    /// The image of `Y` under the rotation and scale.
+    pub y: Vec3,
+    /// The image of `Z` under the rotation and scale.
+    pub z: Vec3,
+    /// The translation, which is the image of `ORIGIN`.
+    pub w: Vec3,
+}
+
+impl Transform {
+    /// The transformation that does nothing.
+    pub const IDENTITY: Transform = Transform {
+        x: X,
+        y: Y,
+        z: Z,
+        w: ORIGIN,
+    };
+
+    /// Construct a transformation that moves points along a vector.
+    pub fn translation(vec: Vec3) -> Transform {
+        Transform {
+            w: vec,
+            ..Transform::IDENTITY
+        }
+    }
+
+    /// Construct a transformation that rotates points counter-clockwise around an axis
+    /// through the origin, by an angle in radians.
+    pub fn rotation(axis: Vec3, angle: f32) -> Transform {
+        let k = axis.normalized();
+        let (sin, cos) = angle.sin_cos();
+        let column = |e: Vec3, k_cross_e: Vec3, k_dot_e: f32| {
+            e * cos + k_cross_e * sin + k * (k_dot_e * (1.0 - cos))
+        };
+        let vec = |x, y, z| Vec3 { x, y, z };
+        Transform {
+            x: column(X, vec(0.0, k.z, -k.y), k.x),
+            y: column(Y, vec(-k.z, 0.0, k.x), k.y),
+            z: column(Z, vec(k.y, -k.x, 0.0), k.z),
+            w: ORIGIN,
+        }
+    }
+
+    /// Construct a transformation that scales points away from the origin by a positive factor.
+    pub fn scaling(factor: f32) -> Transform {
+        assert!(factor > 0.0, "scale factor must be positive");
+        Transform {
+            x: X * factor,
+            y: Y * factor,
+            z: Z * factor,
+            w: ORIGIN,
+        }
+    }
+
+    /// Get the factor that this transformation scales distances by.
+    #[must_use]
+    pub fn scale(self) -> f32 {
+        self.x.length()
+    }
+
+    /// Get the transformation that applies `other` first, and then this one.
+    pub fn compose(self, other: Transform) -> Transform {
+        Transform {
+            x: self.transform_vector(other.x),
+            y: self.transform_vector(other.y),
+            z: self.transform_vector(other.z),
+            w: self.transform_point(other.w),
+        }
+    }
+
+    /// Apply this transformation to a point.
+    pub fn transform_point<S: Scalar>(self, p: Vector3<S>) -> Vector3<S> {
+        self.transform_vector(p) + Vector3::from_f32(self.w)
+    }
+
+    /// Apply this transformation to a direction or displacement, which isn't translated.
+    pub fn transform_vector<S: Scalar>(self, v: Vector3<S>) -> Vector3<S> {
+        let column = Vector3::from_f32;
+        column(self.x) * v.x + column(self.y) * v.y + column(self.z) * v.z
+    }
+
+    /// Apply this transformation to a unit surface normal, keeping it unit length.
+    /// Similarities preserve angles, so normals are transformed like any other direction.
+    pub fn transform_normal<S: Scalar>(self, n: Vector3<S>) -> Vector3<S> {
+        self.transform_vector(n).normalized()
+    }
+
+    /// Get the columns of this transformation as a 4x4 matrix in homogeneous coordinates,
+    /// with the same layout as a WGSL `mat4x4<f32>`.
+    pub fn to_mat4(self) -> [Vec4; 4] {
+        [
+            self.x.extend(0.0),
+            self.y.extend(0.0),
+            self.z.extend(0.0),
+            self.w.extend(1.0),
+        ]
+    }
+
+    /// Get the transformation that undoes this one.
+    pub fn inverse(self) -> Transform {
+        // the inverse of a rotation is its transpose, and the scale is divided out twice
+        let squared_scale = self.x.dot(self.x);
+        let transpose = Transform {
+            x: Vec3 {
+                x: self.x.x,
+                y: self.y.x,
+                z: self.z.x,
+            },
+            y: Vec3 {
+                x: self.x.y,
+                y: self.y.y,
+                z: self.z.y,
+            },
+            z: Vec3 {
+                x: self.x.z,
+                y: self.y.z,
+                z: self.z.z,
+            },
+            w: ORIGIN,
+        };
+        let linear = Transform {
+            x: transpose.x / squared_scale,
+            y: transpose.y / squared_scale,
+            z: transpose.z / squared_scale,
+            w: ORIGIN,
+        };
+        Transform {
+            w: -linear.transform_point(self.w),
+            ..linear
+        }
+    }
+}
+
+/// A deterministic source of smooth noise.
+/// Lattice gradients are chosen by integer hashing, and the rest is basic arithmetic done in the
+/// same order as `shader.wgsl`, so the CPU and GPU agree bit for bit unless the GPU fuses
+/// multiply-adds.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Noise {
+    /// Perlin's improved gradient noise with the given seed, which is zero at integer points.
+    Gradient(u32),
+}
+
+impl Noise {
+    /// A bound on the length of the gradient of `sample`.
+    pub const LIPSCHITZ: f32 = 4.0;
+
+    /// Sample the noise at a point. The result is roughly in `[-1, 1]`.
+    #[must_use]
+    pub fn sample<S: Scalar>(self, p: Vector3<S>) -> S {
+        match self {
+            Noise::Gradient(seed) => gradient_noise(p, seed),
+        }
+    }
+
+    /// Sum `octaves` samples of the noise, each with double the frequency and half the amplitude
+    /// of the last, and each with a different seed.
+    #[must_use]
+    pub fn fbm<S: Scalar>(self, p: Vector3<S>, octaves: u32) -> S {
+        let Noise::Gradient(seed) = self;
+        let mut sum = S::ZERO;
+        let mut scale = S::ONE;
+        for octave in 0..octaves {
+            sum = sum + gradient_noise(p * scale, seed.wrapping_add(octave)) / scale;
+            scale = scale + scale;
+        }
+        sum
+    }
+}
+
+/// Hash a lattice point and a seed, using the finalizer from Murmur3.
+fn hash(x: u32, y: u32, z: u32, seed: u32) -> u32 {
+    let mut h = seed
+        ^ x.wrapping_mul(0x8da6_b343)
+        ^ y.wrapping_mul(0xd816_3841)
+        ^ z.wrapping_mul(0xcb1a_b31f);
+    h ^= h >> 16;
+    h = h.wrapping_mul(0x85eb_ca6b);
+    h ^= h >> 13;
+    h = h.wrapping_mul(0xc2b2_ae35);
+    h ^= h >> 16;
+    h
+}
+
+/// Dot one of the 12 edge directions of a cube, chosen by `hash`, with `offset`.
+fn gradient<S: Scalar>(hash: u32, offset: Vector3<S>) -> S {
+    let h = hash & 15;
+    let u = if h < 8 { offset.x } else { offset.y };
+    let v = if h < 4 {
+        offset.y
+    } else if h == 12 || h == 14 {
+        offset.x
+    } else {
+        offset.z
+    };
+    (if h & 1 == 0 { u } else { -u }) + (if h & 2 == 0 { v } else { -v })
+}
+
+fn fade<S: Scalar>(t: S) -> S {
+    let scalar = S::from_f32;
+    t * t * t * (t * (t * scalar(6.0) - scalar(15.0)) + scalar(10.0))
+}
+
+fn lerp<S: Scalar>(a: S, b: S, t: S) -> S {
+    a + t * (b - a)
+}
+
+fn gradient_noise<S: Scalar>(p: Vector3<S>, seed: u32) -> S {
+    let cell = p.unary(S::floor);
+    let local = p - cell;
+    let lattice = [cell.x, cell.y, cell.z].map(|c| c.floor_to_i32() as u32);
+    let corner = |dx: u32, dy: u32, dz: u32| {
+        let h = hash(
+            lattice[0].wrapping_add(dx),
+            lattice[1].wrapping_add(dy),
+            lattice[2].wrapping_add(dz),
+            seed,
+        );
+        let offset = Vector3::from_f32(Vec3 {
+            x: dx as f32,
+            y: dy as f32,
+            z: dz as f32,
+        });
+        gradient(h, local - offset)
+    };
+    let fade = local.unary(fade);
+    lerp(
+        lerp(
+            lerp(corner(0, 0, 0), corner(1, 0, 0), fade.x),
+            lerp(corner(0, 1, 0), corner(1, 1, 0), fade.x),
+            fade.y,
+        ),
+        lerp(
+            lerp(corner(0, 0, 1), corner(1, 0, 1), fade.x),
+            lerp(corner(0, 1, 1), corner(1, 1, 1), fade.x),
+            fade.y,
+        ),
+        fade.z,
+    )
+}
@@ -0,0 +1,672 @@
+//! Peer-to-peer deterministic lockstep between two players over UDP.
+//!
+//! Each peer sends its own inputs to the other, and only steps a tick once it has both players'
+//! inputs for it, so both simulations see exactly the same inputs and stay in sync. Local inputs
+//! are scheduled a few ticks ahead (the input delay), which hides the latency as long as the
+//! remote inputs arrive within that time. Otherwise the session stalls until they do.
+//!
+//! UDP drops and reorders packets, so every packet repeats all of the inputs that the other peer
+//! hasn't acknowledged yet, and a lost packet is covered by the next one. Every packet also carries
+//! the checksums of the last few checkpoints, so that the peers notice if they ever diverge.
+//!
+//! Packet layout, with all integers little-endian:
+//! - the magic bytes `LS` and a `u8` version,
+//! - a `u64` ack, which is the number of the receiver's inputs that the sender has,
+//! - the `u64` tick of the first input, a `u8` count, and that many `u8`s of `InputFrame` bits,
+//! - a `u8` count, and that many checkpoints, each of which is a `u64` tick and a `u64` checksum.
+
+use crate::*;
+use std::collections::{BTreeMap, VecDeque};
+use std::io;
+use std::net::UdpSocket;
+use std::time::{Duration, Instant};
+
+const MAGIC: [u8; 2] = *b"LS";
+const VERSION: u8 = 1;
+
+/// The number of players in a session.
+pub const PLAYERS: usize = 2;
+
+/// The most inputs that one packet repeats. A peer can't get further ahead of what the other peer
+/// has acknowledged than its input delay and prediction window, so this is only reached after a
+/// long outage.
+const MAX_INPUTS_PER_PACKET: usize = 64;
+
+/// The number of ticks between checkpoints, whose checksums the peers compare.
+pub const CHECKPOINT_INTERVAL: u64 = 30;
+
+/// Check whether the state after `tick` ticks is a checkpoint.
+#[must_use]
+pub fn is_checkpoint(tick: u64) -> bool {
+    tick.is_multiple_of(CHECKPOINT_INTERVAL)
+}
+
+/// The number of recent checkpoints that every packet repeats.
+const CHECKPOINTS_PER_PACKET: usize = 4;
+
+/// The largest packet that a session sends.
+const MAX_PACKET_SIZE: usize =
+    3 + 8 + 8 + 1 + MAX_INPUTS_PER_PACKET + 1 + 16 * CHECKPOINTS_PER_PACKET;
+
+/// A way of exchanging unreliable, unordered packets with the other peer.
+pub trait Transport {
+    /// Send a packet, which may be lost.
+    fn send(&mut self, packet: &[u8]) -> io::Result<()>;
+
+    /// Receive the next packet into `buffer` without blocking, and get its length, or `None` if
+    /// there isn't one yet.
+    fn recv(&mut self, buffer: &mut [u8]) -> io::Result<Option<usize>>;
+}
+
+/// A socket that is connected to the other peer and set to nonblocking.
+impl Transport for UdpSocket {
+    fn send(&mut self, packet: &[u8]) -> io::Result<()> {
+        match UdpSocket::send(self, packet) {
+            // the other peer isn't listening yet, which is the same as losing the packet
+            Err(e) if e.kind() == io::ErrorKind::ConnectionRefused => Ok(()),
+            result => result.map(|_| ()),
+        }
+    }
+
+    fn recv(&mut self, buffer: &mut [u8]) -> io::Result<Option<usize>> {
+        match UdpSocket::recv(self, buffer) {
+            Ok(len) => Ok(Some(len)),
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::ConnectionRefused
+                ) =>
+            {
+                Ok(None)
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// Wraps a transport to simulate a bad network, by dropping some of the packets that it sends and
+/// delaying the rest, by different amounts so that they arrive out of order.
+pub struct LossyLink<T: Transport> {
+    inner: T,
+    loss: Fixed,
+    latency: Duration,
+    jitter: Duration,
+    rng: Rng,
+    in_flight: Vec<(Instant, Vec<u8>)>,
+    /// The number of packets that were sent.
+    pub sent: u64,
+    /// The number of packets that were dropped.
+    pub dropped: u64,
+}
+
+impl<T: Transport> LossyLink<T> {
+    /// Drop a `loss` fraction of packets, and delay the rest by `latency` plus up to `jitter`.
+    /// The same seed drops the same packets.
+    pub fn new(inner: T, loss: Fixed, latency: Duration, jitter: Duration, seed: u64) -> Self {
+        LossyLink {
+            inner,
+            loss,
+            latency,
+            jitter,
+            rng: Rng::new(seed),
+            in_flight: vec![],
+            sent: 0,
+            dropped: 0,
+        }
+    }
+
+    /// Send the packets whose delay is up.
+    fn flush(&mut self) -> io::Result<()> {
+        let now = Instant::now();
+        let (due, waiting) = std::mem::take(&mut self.in_flight)
+            .into_iter()
+            .partition(|(time, _)| *time <= now);
+        self.in_flight = waiting;
+        for (_, packet) in due {
+            self.inner.send(&packet)?;
+        }
+        Ok(())
+    }
+}
+
+impl<T: Transport> Transport for LossyLink<T> {
+    fn send(&mut self, packet: &[u8]) -> io::Result<()> {
+        self.sent += 1;
+        if self.rng.next_fixed() < self.loss {
+            self.dropped += 1;
+        } else {
+            let jitter = self.jitter.mul_f32(self.rng.next_fixed().to_f32());
+            let time = Instant::now() + self.latency + jitter;
+            self.in_flight.push((time, packet.to_vec()));
+        }
+        self.flush()
+    }
+
+    fn recv(&mut self, buffer: &mut [u8]) -> io::Result<Option<usize>> {
+        self.flush()?;
+        self.inner.recv(buffer)
+    }
+}
+
+/// The reasons that a session can fail.
+#[derive(Debug)]
+pub enum NetError {
+    /// The transport failed.
+    Io(io::Error),
+    /// The peers' simulations diverged. The expected checksum is the other peer's.
+    Desync(Divergence),
+}
+
+impl std::fmt::Display for NetError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            NetError::Io(e) => write!(f, "network error: {e}"),
+            NetError::Desync(divergence) => write!(f, "peers {divergence}"),
+        }
+    }
+}
+
+impl From<io::Error> for NetError {
+    fn from(e: io::Error) -> NetError {
+        NetError::Io(e)
+    }
+}
+
+impl std::error::Error for NetError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            NetError::Io(e) => Some(e),
+            NetError::Desync(_) => None,
+        }
+    }
+}
+
+/// One peer's side of a lockstep session.
+///
+/// Every tick, the game queues the local input with `add_local_input`, steps the simulation for
+/// each tick that `next_inputs` returns, reports its checksum with `report_checksum`, and calls
+/// `poll` to exchange packets.
+pub struct Session<T: Transport> {
+    transport: T,
+    local: usize,
+    delay: u64,
+    window: u64,
+    inputs: [Vec<InputFrame>; PLAYERS], // by player, and then by tick
+    tick: u64,
+    remote_ack: u64,
+    checkpoints: BTreeMap<u64, u64>, // local checksums that haven't been compared yet, by tick
+    remote_checkpoints: BTreeMap<u64, u64>, // and the other peer's
+    verified: u64,                   // the last checkpoint that was compared
+    recent: VecDeque<(u64, u64)>,
+    stalls: u64,
+    stalled: bool, // whether the current tick has been counted as a stall
+}
+
+impl<T: Transport> Session<T> {
+    /// Start a session at tick zero as player `local`, which is 0 or 1, with local inputs taking
+    /// effect `delay` ticks after they are added.
+    pub fn new(transport: T, local: usize, delay: u64) -> Session<T> {
+        assert!(local < PLAYERS, "there are only {PLAYERS} players");
+        let mut inputs: [Vec<InputFrame>; PLAYERS] = Default::default();
+        // nobody has pressed anything before the first input arrives
+        for player in &mut inputs {
+            player.resize(delay as usize, InputFrame::default());
+        }
+        Session {
+            transport,
+            local,
+            delay,
+            window: 0,
+            inputs,
+            tick: 0,
+            remote_ack: 0,
+            checkpoints: BTreeMap::new(),
+            remote_checkpoints: BTreeMap::new(),
+            verified: 0,
+            recent: VecDeque::new(),
+            stalls: 0,
+            stalled: false,
+        }
+    }
+
+    /// Let local inputs run up to `window` more ticks ahead of the confirmed ones, which are the
+    /// ones that have both players' inputs, so that a rollback session can predict them.
+    #[must_use]
+    pub fn with_prediction_window(mut self, window: u64) -> Session<T> {
+        self.window = window;
+        self
+    }
+
+    /// Get the transport.
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
+
+    /// Get the number of ticks that have run, which are the ones that have been confirmed.
+    #[must_use]
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    /// Get the input delay, in ticks.
+    #[must_use]
+    pub fn delay(&self) -> u64 {
+        self.delay
+    }
+
+    /// Get the local player.
+    #[must_use]
+    pub fn local(&self) -> usize {
+        self.local
+    }
+
+    /// Get the inputs that a player has added or sent so far, by tick.
+    #[must_use]
+    pub fn inputs(&self, player: usize) -> &[InputFrame] {
+        &self.inputs[player]
+    }
+
+    /// Get the number of ticks that `next_inputs` had to wait for the other peer's inputs for.
+    #[must_use]
+    pub fn stalls(&self) -> u64 {
+        self.stalls
+    }
+
+    /// Get the other peer's player.
+    #[must_use]
+    pub fn remote(&self) -> usize {
+        1 - self.local
+    }
+
+    /// Queue the local player's input for the tick `delay` ticks from now. Returns false, and
+    /// drops the input, if the session is stalled and already has inputs queued that far ahead,
+    /// plus the prediction window.
+    pub fn add_local_input(&mut self, input: InputFrame) -> bool {
+        let local = &mut self.inputs[self.local];
+        if local.len() as u64 > self.tick + self.delay + self.window {
+            return false;
+        }
+        local.push(input);
+        true
+    }
+
+    /// Get both players' inputs for the next tick, which counts as run once they are returned.
+    /// Returns `None` until a local input has been added for `delay` ticks after it, and while the
+    /// other peer's input hasn't arrived.
+    pub fn next_inputs(&mut self) -> Option<[InputFrame; PLAYERS]> {
+        // each local input moves the clock on by a tick, and the simulation runs `delay` behind it
+        if self.inputs[self.local].len() as u64 <= self.tick + self.delay {
+            return None;
+        }
+        let tick = self.tick as usize;
+        if self.inputs[self.remote()].len() <= tick {
+            self.stalls += u64::from(!self.stalled);
+            self.stalled = true;
+            return None;
+        }
+        self.tick += 1;
+        self.stalled = false;
+        Some([self.inputs[0][tick], self.inputs[1][tick]])
+    }
+
+    /// Report the checksum of the simulation after `tick` confirmed ticks have run. Only every few
+    /// ticks are checkpoints, which are compared with the other peer.
+    pub fn report_checksum(&mut self, tick: u64, checksum: u64) -> Result<(), NetError> {
+        if !is_checkpoint(tick) {
+            return Ok(());
+        }
+        self.checkpoints.insert(tick, checksum);
+        self.recent.push_back((tick, checksum));
+        if self.recent.len() > CHECKPOINTS_PER_PACKET {
+            self.recent.pop_front();
+        }
+        self.compare_checkpoints()
+    }
+
+    /// Compare the checkpoints that both peers have reached, and forget them if they match.
+    fn compare_checkpoints(&mut self) -> Result<(), NetError> {
+        while let Some((&tick, &expected)) = self.remote_checkpoints.first_key_value() {
+            let Some(&actual) = self.checkpoints.get(&tick) else {
+                break;
+            };
+            if actual != expected {
+                return Err(NetError::Desync(Divergence {
+                    tick,
+                    expected,
+                    actual,
+                }));
+            }
+            self.verified = tick;
+            self.remote_checkpoints.remove(&tick);
+            self.checkpoints.retain(|&t, _| t > tick);
+        }
+        Ok(())
+    }
+
+    /// Receive every packet that has arrived, and then send one to the other peer.
+    pub fn poll(&mut self) -> Result<(), NetError> {
+        let mut buffer = [0; MAX_PACKET_SIZE];
+        while let Some(len) = self.transport.recv(&mut buffer)? {
+            // anything malformed is as good as lost
+            if let Ok(packet) = Packet::decode(&buffer[..len]) {
+                self.receive(&packet)?;
+            }
+        }
+        let packet = self.packet();
+        self.transport.send(&packet.encode())?;
+        Ok(())
+    }
+
+    fn receive(&mut self, packet: &Packet) -> Result<(), NetError> {
+        self.remote_ack = self.remote_ack.max(packet.ack);
+
+        // packets can arrive out of order, so only take the inputs that follow on from ours
+        let remote = self.remote();
+        let have = self.inputs[remote].len() as u64;
+        if packet.first_tick <= have {
+            let skip = (have - packet.first_tick) as usize;
+            self.inputs[remote].extend(packet.inputs.iter().skip(skip));
+        }
+
+        for &(tick, checksum) in &packet.checkpoints {
+            if tick > self.verified {
+                self.remote_checkpoints.insert(tick, checksum);
+            }
+        }
+        self.compare_checkpoints()
+    }
+
+    fn packet(&self) -> Packet {
+        let first_tick = self.remote_ack;
+        Packet {
+            ack: self.inputs[self.remote()].len() as u64,
+            first_tick,
+            inputs: self.inputs[self.local]
+                .iter()
+                .skip(first_tick as usize)
+                .take(MAX_INPUTS_PER_PACKET)
+                .copied()
+                .collect(),
+            checkpoints: self.recent.iter().copied().collect(),
+        }
+    }
+}
+
+/// The contents of a packet.
+struct Packet {
+    ack: u64,
+    first_tick: u64,
+    inputs: Vec<InputFrame>,
+    checkpoints: Vec<(u64, u64)>,
+}
+
+impl Packet {
+    fn encode(&self) -> Vec<u8> {
+        let mut out = MAGIC.to_vec();
+        out.push(VERSION);
+        out.extend_from_slice(&self.ack.to_le_bytes());
+        out.extend_from_slice(&self.first_tick.to_le_bytes());
+        out.push(self.inputs.len() as u8);
+        out.extend(self.inputs.iter().map(|input| input.to_bits()));
+        out.push(self.checkpoints.len() as u8);
+        for (tick, checksum) in &self.checkpoints {
+            out.extend_from_slice(&tick.to_le_bytes());
+            out.extend_from_slice(&checksum.to_le_bytes());
+        }
+        out
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Packet, PacketError> {
+        let mut r = Reader(bytes);
+        if r.take::<2>()? != MAGIC {
+            return Err(PacketError::BadMagic);
+        }
+        match r.u8()? {
+            VERSION => {}
+            version => return Err(PacketError::UnsupportedVersion(version)),
+        }
+        let ack = r.u64()?;
+        let first_tick = r.u64()?;
+        let inputs = (0..r.u8()?)
+            .map(|_| {
+                let bits = r.u8()?;
+                InputFrame::from_bits(bits).ok_or(PacketError::UnknownButtons(bits))
+            })
+            .collect::<Result<_, _>>()?;
+        let checkpoints = (0..r.u8()?)
+            .map(|_| Ok((r.u64()?, r.u64()?)))
+            .collect::<Result<_, PacketError>>()?;
+        if !r.0.is_empty() {
+            return Err(PacketError::TrailingBytes);
+        }
+        Ok(Packet {
+            ack,
+            first_tick,
+            inputs,
+            checkpoints,
+        })
+    }
+}
+
+/// The reasons that `Packet::decode` can reject a packet.
+#[derive(Debug, PartialEq)]
+enum PacketError {
+    /// The packet ended in the middle of a value.
+    UnexpectedEnd,
+    /// The packet doesn't start with the magic bytes.
+    BadMagic,
+    /// The packet has a version that this build can't read.
+    UnsupportedVersion(u8),
+    /// An input frame had bits for unknown buttons.
+    UnknownButtons(u8),
+    /// There were bytes after the last checkpoint.
+    TrailingBytes,
+}
+
+impl std::fmt::Display for PacketError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            PacketError::UnexpectedEnd => write!(f, "unexpected end of packet"),
+            PacketError::BadMagic => write!(f, "not a lockstep packet"),
+            PacketError::UnsupportedVersion(version) => {
+                write!(f, "unsupported packet version {version}")
+            }
+            PacketError::UnknownButtons(bits) => write!(f, "unknown buttons in {bits:08b}"),
+            PacketError::TrailingBytes => write!(f, "trailing bytes after the last checkpoint"),
+        }
+    }
+}
+
+impl From<UnexpectedEnd> for PacketError {
+    fn from(_: UnexpectedEnd) -> PacketError {
+        PacketError::UnexpectedEnd
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    /// One end of an in-memory connection, which delivers every packet in order.
+    struct Pipe {
+        outbox: Rc<RefCell<VecDeque<Vec<u8>>>>,
+        inbox: Rc<RefCell<VecDeque<Vec<u8>>>>,
+    }
+
+    impl Transport for Pipe {
+        fn send(&mut self, packet: &[u8]) -> io::Result<()> {
+            self.outbox.borrow_mut().push_back(packet.to_vec());
+            Ok(())
+        }
+
+        fn recv(&mut self, buffer: &mut [u8]) -> io::Result<Option<usize>> {
+            Ok(self.inbox.borrow_mut().pop_front().map(|packet| {
+                buffer[..packet.len()].copy_from_slice(&packet);
+                packet.len()
+            }))
+        }
+    }
+
+    /// Connect two pipes to each other, through links with fixed seeds that drop a fifth of the
+    /// packets and delay the rest by up to 3 ms, so that they arrive out of order.
+    fn lossy_pipes() -> [LossyLink<Pipe>; PLAYERS] {
+        let queues = [Rc::default(), Rc::default()];
+        [0, 1].map(|player: usize| {
+            let pipe = Pipe {
+                outbox: Rc::clone(&queues[1 - player]),
+                inbox: Rc::clone(&queues[player]),
+            };
+            let loss = Fixed::ONE / Fixed::from_int(5);
+            LossyLink::new(
+                pipe,
+                loss,
+                Duration::ZERO,
+                Duration::from_millis(3),
+                player as u64,
+            )
+        })
+    }
+
+    /// Connect two nonblocking UDP sockets on localhost to each other.
+    fn udp_sockets() -> [UdpSocket; PLAYERS] {
+        let sockets = [0, 1].map(|_| {
+            let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+            socket.set_nonblocking(true).unwrap();
+            socket
+        });
+        sockets[0]
+            .connect(sockets[1].local_addr().unwrap())
+            .unwrap();
+        sockets[1]
+            .connect(sockets[0].local_addr().unwrap())
+            .unwrap();
+        sockets
+    }
+
+    /// Play both sides of a lockstep session with random inputs until both have run `ticks`
+    /// ticks, and check that they did, with the same checksum at every checkpoint.
+    fn play_lockstep<T: Transport>(transports: [T; PLAYERS], ticks: u64) -> [Session<T>; PLAYERS] {
+        let mut transports = transports.into_iter();
+        let mut peers = [0, 1].map(|player: usize| {
+            let session = Session::new(transports.next().unwrap(), player, 4);
+            (session, World::new(), Bot::new(player as u64), None, vec![])
+        });
+        while peers.iter().any(|(session, ..)| session.tick() < ticks) {
+            std::thread::sleep(Duration::from_millis(1));
+            for (session, world, bot, pending, checkpoints) in &mut peers {
+                // a stalled session drops the input, so it is kept for the next try
+                if (session.inputs(session.local()).len() as u64) < ticks + session.delay() {
+                    let input = *pending.get_or_insert_with(|| bot.next_input());
+                    if session.add_local_input(input) {
+                        *pending = None;
+                    }
+                }
+                while let Some(inputs) = session.next_inputs() {
+                    world.step(&inputs);
+                    let checksum = world.checksum();
+                    if is_checkpoint(session.tick()) {
+                        checkpoints.push((session.tick(), checksum));
+                    }
+                    session.report_checksum(session.tick(), checksum).unwrap();
+                }
+                session.poll().unwrap();
+            }
+        }
+
+        let [(a, .., a_checkpoints), (b, .., b_checkpoints)] = &peers;
+        assert_eq!(a.tick(), ticks);
+        assert_eq!(b.tick(), ticks);
+        assert_eq!(a_checkpoints.len() as u64, ticks / CHECKPOINT_INTERVAL);
+        assert_eq!(a_checkpoints, b_checkpoints);
+        peers.map(|(session, ..)| session)
+    }
+
+    #[test]
+    fn lockstep_peers_stay_in_sync_over_a_lossy_link() {
+        let [a, b] = play_lockstep(lossy_pipes(), 300);
+        assert!(a.transport().dropped > 0 && b.transport().dropped > 0);
+    }
+
+    #[test]
+    fn lockstep_peers_stay_in_sync_over_udp() {
+        play_lockstep(udp_sockets(), 120);
+    }
+
+    #[test]
+    fn rollback_peers_converge_over_a_lossy_link() {
+        const TICKS: u64 = 300;
+        let mut links = lossy_pipes().into_iter();
+        let mut peers = [0, 1].map(|player: usize| {
+            let rollback = Rollback::new(links.next().unwrap(), player, 1, 8, World::new());
+            (rollback, Bot::new(player as u64), None)
+        });
+        while peers
+            .iter()
+            .any(|(rollback, ..)| rollback.session().tick() < TICKS)
+        {
+            std::thread::sleep(Duration::from_millis(1));
+            for (rollback, bot, pending) in &mut peers {
+                // a stalled session drops the input, so it is kept for the next try
+                let session = rollback.session();
+                if (session.inputs(session.local()).len() as u64) < TICKS + session.delay() {
+                    let input = *pending.get_or_insert_with(|| bot.next_input());
+                    if rollback.add_local_input(input) {
+                        *pending = None;
+                    }
+                }
+                rollback.poll().unwrap();
+            }
+        }
+
+        // once every tick is confirmed, neither simulation is running on predictions
+        let [(a, ..), (b, ..)] = &peers;
+        assert_eq!(a.tick(), TICKS);
+        assert_eq!(b.tick(), TICKS);
+        assert_eq!(a.simulation().checksum(), b.simulation().checksum());
+        assert!(a.rollbacks() + b.rollbacks() > 0);
+    }
+
+    fn packet() -> Packet {
+        Packet {
+            ack: 5,
+            first_tick: 3,
+            inputs: vec![InputFrame::default(); 4],
+            checkpoints: vec![(30, 0x1234), (60, 0x5678)],
+        }
+    }
+
+    #[test]
+    fn packets_round_trip() {
+        let bytes = packet().encode();
+        let decoded = Packet::decode(&bytes).unwrap();
+        assert_eq!(decoded.encode(), bytes);
+        assert_eq!(decoded.checkpoints, packet().checkpoints);
+    }
+
+    #[test]
+    fn rejects_malformed_packets() {
+        let bytes = packet().encode();
+        let corrupt = |i: usize, byte: u8| {
+            let mut bytes = bytes.clone();
+            bytes[i] = byte;
+            Packet::decode(&bytes).err()
+        };
+        assert_eq!(corrupt(0, b'X'), Some(PacketError::BadMagic));
+        assert_eq!(corrupt(2, 9), Some(PacketError::UnsupportedVersion(9)));
+        assert_eq!(corrupt(20, 0xff), Some(PacketError::UnknownButtons(0xff)));
+        for len in 0..bytes.len() {
+            assert_eq!(
+                Packet::decode(&bytes[..len]).err(),
+                Some(PacketError::UnexpectedEnd)
+            );
+        }
+        let mut long = bytes.clone();
+        long.push(0);
+        assert_eq!(
+            Packet::decode(&long).err(),
+            Some(PacketError::TrailingBytes)
+        );
+    }
+}
@@ -0,0 +1,695 @@
+//! Rigid-body physics against SDF colliders, in fixed point so that every peer gets the same
+//! result.
+//!
+//! Each body samples points on the surface of its shape once, when it is created. Two bodies
+//! touch where the points of one are inside the other's `Sdf`, and that `Sdf`'s gradient there is
+//! the normal of the contact. Bodies also touch the terrain, which is a static `Sdf` in world
+//! space for the islands and docks. Contacts are resolved with impulses, for restitution and
+//! friction, and then the bodies are pushed apart. Bodies only turn about `Y`, like `Pose`.
+
+use crate::*;
+
+/// The number of times that the solver goes over the contacts every tick.
+const ITERATIONS: usize = 4;
+
+/// The number of steps that `Collider::new` marches from the bounding sphere to the surface.
+const MARCH_STEPS: usize = 32;
+
+/// How deep contacts may be without pushing the bodies apart, which keeps resting contacts from
+/// jittering.
+fn slop() -> Fixed {
+    Fixed::ONE / Fixed::from_int(200)
+}
+
+/// How much of the depth of a contact is corrected every tick.
+fn correction() -> Fixed {
+    Fixed::from_int(4) / Fixed::from_int(5)
+}
+
+/// Where an entity is, and which way it faces.
+///
+/// This isn't a `Transform`, because the simulation has to be in `Fixed` to be deterministic,
+/// and `Transform` is an `f32` matrix for scenes and rendering. Entities also only turn about
+/// `Y`, so a heading is all the rotation that they need, and it can't drift into a shear.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Pose {
+    /// The position, with y up.
+    pub position: FixedVec3,
+    /// The angle from `Z` towards `X` around `Y`, in radians.
+    pub heading: Fixed,
+}
+
+impl Pose {
+    /// Rotate a vector in this pose's frame into the world's frame.
+    pub fn rotate(&self, v: FixedVec3) -> FixedVec3 {
+        let (sin, cos) = self.heading.sin_cos();
+        FixedVec3 {
+            x: v.x * cos + v.z * sin,
+            y: v.y,
+            z: v.z * cos - v.x * sin,
+        }
+    }
+
+    /// Rotate a vector in the world's frame into this pose's frame.
+    pub fn unrotate(&self, v: FixedVec3) -> FixedVec3 {
+        let (sin, cos) = self.heading.sin_cos();
+        FixedVec3 {
+            x: v.x * cos - v.z * sin,
+            y: v.y,
+            z: v.z * cos + v.x * sin,
+        }
+    }
+
+    /// Get the direction that this pose faces.
+    pub fn forward(&self) -> FixedVec3 {
+        self.rotate(FixedVec3::from_f32(Z))
+    }
+
+    /// Get the pose of something at `offset` in this pose's frame, facing the same way.
+    #[must_use]
+    pub fn then(&self, offset: FixedVec3) -> Pose {
+        Pose {
+            position: self.position + self.rotate(offset),
+            heading: self.heading,
+        }
+    }
+
+    /// Convert a point in the world's frame into this pose's frame.
+    pub fn to_local(&self, p: FixedVec3) -> FixedVec3 {
+        self.unrotate(p - self.position)
+    }
+}
+
+impl StableHash for Pose {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        hasher.field("position", &self.position);
+        hasher.field("heading", &self.heading);
+    }
+}
+
+/// How a rigid body moves. Its shape is the entity's `Sdf`, and it needs a `Collider` to touch
+/// anything.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Body {
+    /// The velocity, in units per second.
+    pub velocity: FixedVec3,
+    /// The angular velocity about `Y`, in radians per second.
+    pub spin: Fixed,
+    /// One over the mass, which is zero for bodies that nothing can move.
+    pub inverse_mass: Fixed,
+    /// One over the moment of inertia about `Y`, which is zero for bodies that nothing can turn.
+    pub inverse_inertia: Fixed,
+    /// How much gravity pulls on it, where one is fully and zero is for things that float.
+    pub gravity: Fixed,
+    /// How much of its speed into a contact it bounces back with, from zero to one.
+    pub restitution: Fixed,
+    /// The coefficient of friction of its surface.
+    pub friction: Fixed,
+}
+
+impl Body {
+    /// Make a body with some mass and moment of inertia about `Y`, at rest, that falls, doesn't
+    /// bounce, and has a coefficient of friction of one half.
+    #[must_use]
+    pub fn new(mass: Fixed, inertia: Fixed) -> Body {
+        let inverse = |x: Fixed| {
+            if x > Fixed::ZERO {
+                Fixed::ONE / x
+            } else {
+                Fixed::ZERO
+            }
+        };
+        Body {
+            velocity: FixedVec3::default(),
+            spin: Fixed::ZERO,
+            inverse_mass: inverse(mass),
+            inverse_inertia: inverse(inertia),
+            gravity: Fixed::ONE,
+            restitution: Fixed::ZERO,
+            friction: Fixed::ONE / Fixed::from_int(2),
+        }
+    }
+
+    /// Make a body that nothing can move or turn.
+    #[must_use]
+    pub fn immovable() -> Body {
+        Body {
+            gravity: Fixed::ZERO,
+            ..Body::new(Fixed::ZERO, Fixed::ZERO)
+        }
+    }
+
+    /// Get the velocity of the point `r` away from the body's position.
+    fn velocity_at(&self, r: FixedVec3) -> FixedVec3 {
+        self.velocity
+            + FixedVec3 {
+                x: self.spin * r.z,
+                y: Fixed::ZERO,
+                z: -self.spin * r.x,
+            }
+    }
+
+    /// Get how little an impulse along `direction` at the point `r` away from the body's position
+    /// changes the velocity of that point along it, which is the inverse of its effective mass.
+    fn resistance(&self, r: FixedVec3, direction: FixedVec3) -> Fixed {
+        let torque = torque(r, direction);
+        self.inverse_mass + self.inverse_inertia * torque * torque
+    }
+
+    /// Apply an impulse at the point `r` away from the body's position.
+    fn apply_impulse(&mut self, r: FixedVec3, impulse: FixedVec3) {
+        self.velocity += impulse * self.inverse_mass;
+        self.spin += torque(r, impulse) * self.inverse_inertia;
+    }
+}
+
+impl StableHash for Body {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        hasher.field("velocity", &self.velocity);
+        hasher.field("spin", &self.spin);
+        hasher.field("inverse_mass", &self.inverse_mass);
+        hasher.field("inverse_inertia", &self.inverse_inertia);
+        hasher.field("gravity", &self.gravity);
+        hasher.field("restitution", &self.restitution);
+        hasher.field("friction", &self.friction);
+    }
+}
+
+/// Get the torque about `Y` of a force at `r`.
+fn torque(r: FixedVec3, force: FixedVec3) -> Fixed {
+    r.z * force.x - r.x * force.z
+}
+
+/// Points on the surface of a body's shape, in its own frame, which are tested against the
+/// shapes of other bodies and the terrain.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Collider {
+    radius: Fixed,
+    points: Vec<FixedVec3>,
+}
+
+impl Collider {
+    /// Sample `count` points spread evenly over the surface of `shape`, which must contain the
+    /// origin, and fit in a sphere of `radius` around it. Each point is found by marching in
+    /// from the sphere towards the origin.
+    #[must_use]
+    pub fn new(shape: &Sdf, radius: Fixed, count: usize) -> Collider {
+        let golden_angle = Fixed::PI * (Fixed::from_int(3) - Fixed::from_int(5).sqrt());
+        let count = i32::try_from(count).expect("too many points");
+        let points = (0..count)
+            .map(|i| {
+                // a Fibonacci lattice, which spirals from the top of the sphere to the bottom
+                let i = Fixed::from_int(i);
+                let y = Fixed::ONE - (i + i + Fixed::ONE) / Fixed::from_int(count);
+                let (sin, cos) = (golden_angle * i).sin_cos();
+                let r = (Fixed::ONE - y * y).sqrt();
+                let direction = FixedVec3 {
+                    x: r * cos,
+                    y,
+                    z: r * sin,
+                };
+
+                let mut p = direction * radius;
+                for _ in 0..MARCH_STEPS {
+                    let distance = shape.distance(p);
+                    if distance <= Fixed::ZERO {
+                        break;
+                    }
+                    p -= direction * distance;
+                }
+                p
+            })
+            .collect();
+        Collider { radius, points }
+    }
+
+    /// Get the radius of the sphere that the shape fits in.
+    pub fn radius(&self) -> Fixed {
+        self.radius
+    }
+
+    /// Get the points on the surface of the shape.
+    pub fn points(&self) -> &[FixedVec3] {
+        &self.points
+    }
+}
+
+impl StableHash for Collider {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        hasher.field("radius", &self.radius);
+        hasher.field("points", &self.points);
+    }
+}
+
+/// Where two bodies, or a body and the terrain, touch.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Contact {
+    /// The first body.
+    pub a: Entity,
+    /// The second body, or `None` for the terrain.
+    pub b: Option<Entity>,
+    /// The deepest point of the contact, in the world's frame.
+    pub point: FixedVec3,
+    /// The direction that pushes `a` away from `b`, which has unit length.
+    pub normal: FixedVec3,
+    /// How far `a` is inside `b`.
+    pub depth: Fixed,
+}
+
+impl StableHash for Contact {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        hasher.field("a", &self.a);
+        hasher.field("terrain", &self.b.is_none());
+        if let Some(b) = &self.b {
+            hasher.field("b", b);
+        }
+        hasher.field("point", &self.point);
+        hasher.field("normal", &self.normal);
+        hasher.field("depth", &self.depth);
+    }
+}
+
+/// A body that can touch things, during a step.
+struct Participant<'a> {
+    entity: Entity,
+    pose: Pose,
+    collider: &'a Collider,
+    shape: &'a Sdf,
+}
+
+impl Participant<'_> {
+    /// Find the deepest of this body's points inside `shape`, which is at `pose`, and get a
+    /// contact for it, with the normal from the gradient of `shape`.
+    fn deepest_in(&self, shape: &Sdf, pose: Option<Pose>) -> Option<(FixedVec3, FixedVec3, Fixed)> {
+        let local = |p| pose.map_or(p, |pose| pose.to_local(p));
+        let (point, distance) = self
+            .collider
+            .points
+            .iter()
+            .map(|&p| {
+                let point = self.pose.position + self.pose.rotate(p);
+                (point, shape.distance(local(point)))
+            })
+            .min_by_key(|&(_, distance)| distance)?;
+        if distance >= Fixed::ZERO {
+            return None;
+        }
+
+        let gradient = shape.gradient(local(point));
+        let gradient = pose.map_or(gradient, |pose| pose.rotate(gradient));
+        let length = gradient.length();
+        if length == Fixed::ZERO {
+            return None;
+        }
+        Some((point, gradient / length, -distance))
+    }
+}
+
+/// The rules of physics, and the terrain.
+#[derive(Clone)]
+pub struct Physics {
+    /// The static shape of the islands and docks, in the world's frame.
+    pub terrain: Sdf,
+    /// The acceleration of gravity, in units per second squared.
+    pub gravity: FixedVec3,
+    /// How much of their speed bodies bounce back from the terrain with.
+    pub restitution: Fixed,
+    /// The coefficient of friction of the terrain.
+    pub friction: Fixed,
+}
+
+impl Physics {
+    /// Make the physics for some terrain, with gravity of ten units per second squared down
+    /// `Y`, and terrain that bounces a little and has a coefficient of friction of one half.
+    #[must_use]
+    pub fn new(terrain: Sdf) -> Physics {
+        Physics {
+            terrain,
+            gravity: FixedVec3 {
+                x: Fixed::ZERO,
+                y: Fixed::from_int(-10),
+                z: Fixed::ZERO,
+            },
+            restitution: Fixed::ONE / Fixed::from_int(5),
+            friction: Fixed::ONE / Fixed::from_int(2),
+        }
+    }
+
+    /// Get the body that stands in for the terrain in contacts.
+    fn terrain_body(&self) -> Body {
+        Body {
+            restitution: self.restitution,
+            friction: self.friction,
+            ..Body::immovable()
+        }
+    }
+
+    /// Advance the bodies by one tick: apply gravity, find the contacts between bodies with
+    /// colliders, and with the terrain, resolve them, and move the bodies. Entities with a
+    /// collider but no body are immovable. Returns the contacts, in order of entity.
+    pub fn step(
+        &self,
+        poses: &mut Components<Pose>,
+        bodies: &mut Components<Body>,
+        colliders: &Components<Collider>,
+        shapes: &Components<Sdf>,
+    ) -> Vec<Contact> {
+        let dt = tick_seconds();
+        for (_, body) in bodies.iter_mut() {
+            if body.inverse_mass > Fixed::ZERO {
+                body.velocity += self.gravity * (body.gravity * dt);
+            }
+        }
+
+        let contacts = self.contacts(poses, colliders, shapes);
+        for _ in 0..ITERATIONS {
+            for contact in &contacts {
+                self.resolve(contact, poses, bodies);
+            }
+        }
+
+        for (entity, body) in bodies.iter() {
+            if let Some(pose) = poses.get_mut(entity) {
+                pose.position += body.velocity * dt;
+                pose.heading += body.spin * dt;
+                pose.heading =
+                    Fixed::from_bits(pose.heading.to_bits().rem_euclid(Fixed::TAU.to_bits()));
+            }
+        }
+
+        for contact in &contacts {
+            self.separate(contact, poses, bodies);
+        }
+        contacts
+    }
+
+    /// Find the deepest contact of each pair of bodies that touch, and of each body that touches
+    /// the terrain.
+    fn contacts(
+        &self,
+        poses: &Components<Pose>,
+        colliders: &Components<Collider>,
+        shapes: &Components<Sdf>,
+    ) -> Vec<Contact> {
+        let participants: Vec<_> = colliders
+            .iter()
+            .filter_map(|(entity, collider)| {
+                Some(Participant {
+                    entity,
+                    pose: *poses.get(entity)?,
+                    collider,
+                    shape: shapes.get(entity)?,
+                })
+            })
+            .collect();
+
+        let mut contacts = vec![];
+        for (i, a) in participants.iter().enumerate() {
+            for b in &participants[i + 1..] {
+                let reach = a.collider.radius + b.collider.radius;
+                if (a.pose.position - b.pose.position).length() > reach {
+                    continue;
+                }
+                // a's points in b push a away from b, and b's points in a push b away from a
+                let a_in_b = a.deepest_in(b.shape, Some(b.pose));
+                let b_in_a = b
+                    .deepest_in(a.shape, Some(a.pose))
+                    .map(|(point, normal, depth)| (point, -normal, depth));
+                let deepest = match (a_in_b, b_in_a) {
+                    (Some(x), Some(y)) => Some(if y.2 > x.2 { y } else { x }),
+                    (x, y) => x.or(y),
+                };
+                if let Some((point, normal, depth)) = deepest {
+                    contacts.push(Contact {
+                        a: a.entity,
+                        b: Some(b.entity),
+                        point,
+                        normal,
+                        depth,
+                    });
+                }
+            }
+            if let Some((point, normal, depth)) = a.deepest_in(&self.terrain, None) {
+                contacts.push(Contact {
+                    a: a.entity,
+                    b: None,
+                    point,
+                    normal,
+                    depth,
+                });
+            }
+        }
+        contacts
+    }
+
+    /// Get the pose and body of one side of a contact.
+    fn side(
+        &self,
+        entity: Option<Entity>,
+        poses: &Components<Pose>,
+        bodies: &Components<Body>,
+    ) -> (Pose, Body) {
+        match entity {
+            Some(entity) => (
+                poses.get(entity).copied().unwrap_or_default(),
+                bodies.get(entity).copied().unwrap_or_else(Body::immovable),
+            ),
+            None => (Pose::default(), self.terrain_body()),
+        }
+    }
+
+    /// Apply the impulses that stop the bodies of a contact moving into each other, with
+    /// restitution, and the friction that opposes them sliding.
+    fn resolve(&self, contact: &Contact, poses: &Components<Pose>, bodies: &mut Components<Body>) {
+        let (pose_a, mut a) = self.side(Some(contact.a), poses, bodies);
+        let (pose_b, mut b) = self.side(contact.b, poses, bodies);
+        let ra = contact.point - pose_a.position;
+        let rb = contact.point - pose_b.position;
+        let normal = contact.normal;
+
+        let resistance = a.resistance(ra, normal) + b.resistance(rb, normal);
+        if resistance == Fixed::ZERO {
+            return;
+        }
+
+        let relative = a.velocity_at(ra) - b.velocity_at(rb);
+        let approach = relative.dot(normal);
+        if approach >= Fixed::ZERO {
+            return;
+        }
+        let restitution = Ord::max(a.restitution, b.restitution);
+        let impulse = -(Fixed::ONE + restitution) * approach / resistance;
+        a.apply_impulse(ra, normal * impulse);
+        b.apply_impulse(rb, -(normal * impulse));
+
+        let relative = a.velocity_at(ra) - b.velocity_at(rb);
+        let sliding = relative - normal * relative.dot(normal);
+        let speed = sliding.length();
+        if speed > Fixed::ZERO {
+            let tangent = sliding / speed;
+            let friction = (a.friction * b.friction).sqrt();
+            // a body with infinite mass that can only spin may not be able to move along the
+            // tangent at all, even though it can along the normal
+            let sliding_resistance = a.resistance(ra, tangent) + b.resistance(rb, tangent);
+            if sliding_resistance != Fixed::ZERO {
+                let friction_impulse = Ord::min(speed / sliding_resistance, friction * impulse);
+                a.apply_impulse(ra, -(tangent * friction_impulse));
+                b.apply_impulse(rb, tangent * friction_impulse);
+            }
+        }
+
+        if let Some(body) = bodies.get_mut(contact.a) {
+            *body = a;
+        }
+        if let Some(body) = contact.b.and_then(|b| bodies.get_mut(b)) {
+            *body = b;
+        }
+    }
+
+    /// Push the bodies of a contact apart, in proportion to their inverse masses, so that they
+    /// don't sink into each other over time.
+    fn separate(&self, contact: &Contact, poses: &mut Components<Pose>, bodies: &Components<Body>) {
+        let (_, a) = self.side(Some(contact.a), poses, bodies);
+        let (_, b) = self.side(contact.b, poses, bodies);
+        let total = a.inverse_mass + b.inverse_mass;
+        let depth = contact.depth - slop();
+        if total == Fixed::ZERO || depth <= Fixed::ZERO {
+            return;
+        }
+        let push = contact.normal * (depth * correction() / total);
+        if let Some(pose) = poses.get_mut(contact.a) {
+            pose.position += push * a.inverse_mass;
+        }
+        if let Some(pose) = contact.b.and_then(|b| poses.get_mut(b)) {
+            pose.position -= push * b.inverse_mass;
+        }
+    }
+}
+
+impl StableHash for Physics {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        hasher.field("terrain", &self.terrain);
+        hasher.field("gravity", &self.gravity);
+        hasher.field("restitution", &self.restitution);
+        hasher.field("friction", &self.friction);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The components that `Physics::step` works on, for bodies without the rest of a `World`.
+    struct Scene {
+        physics: Physics,
+        entities: Entities,
+        poses: Components<Pose>,
+        bodies: Components<Body>,
+        colliders: Components<Collider>,
+        shapes: Components<Sdf>,
+    }
+
+    impl Scene {
+        fn new(terrain: Sdf) -> Scene {
+            Scene {
+                physics: Physics::new(terrain),
+                entities: Entities::new(),
+                poses: Components::new(),
+                bodies: Components::new(),
+                colliders: Components::new(),
+                shapes: Components::new(),
+            }
+        }
+
+        /// Add a ball with a radius of one half.
+        fn ball(&mut self, position: FixedVec3, body: Body) -> Entity {
+            let entity = self.entities.spawn();
+            let shape = Sdf::sphere(0.5, Material::Flat(Color::WHITE));
+            self.colliders
+                .insert(entity, Collider::new(&shape, Fixed::ONE, 32));
+            self.shapes.insert(entity, shape);
+            self.poses.insert(
+                entity,
+                Pose {
+                    position,
+                    heading: Fixed::ZERO,
+                },
+            );
+            self.bodies.insert(entity, body);
+            entity
+        }
+
+        fn step(&mut self) -> Vec<Contact> {
+            self.physics.step(
+                &mut self.poses,
+                &mut self.bodies,
+                &self.colliders,
+                &self.shapes,
+            )
+        }
+
+        fn hash(&self) -> u64 {
+            let mut hasher = StableHasher::new();
+            hasher.field("poses", &self.poses);
+            hasher.field("bodies", &self.bodies);
+            hasher.finish()
+        }
+    }
+
+    fn point(x: i32, y: i32, z: i32) -> FixedVec3 {
+        FixedVec3 {
+            x: Fixed::from_int(x),
+            y: Fixed::from_int(y),
+            z: Fixed::from_int(z),
+        }
+    }
+
+    fn ground() -> Sdf {
+        Sdf::plane(Y, 0.0, Material::Flat(Color::WHITE))
+    }
+
+    #[test]
+    fn dropped_body_comes_to_rest_on_flat_terrain() {
+        let mut scene = Scene::new(ground());
+        let ball = scene.ball(point(0, 2, 0), Body::new(Fixed::ONE, Fixed::ONE));
+        // it lands hard, but has settled after a few seconds, hopping by less than a tick of
+        // gravity as it rests
+        for tick in 0..600 {
+            scene.step();
+            if tick < 540 {
+                continue;
+            }
+            let pose = scene.poses.get(ball).unwrap();
+            let collider = scene.colliders.get(ball).unwrap();
+            for &p in collider.points() {
+                let distance = scene
+                    .physics
+                    .terrain
+                    .distance(pose.position + pose.rotate(p));
+                assert!(distance >= -slop(), "tick {tick}: sank {distance:?}");
+            }
+            let body = scene.bodies.get(ball).unwrap();
+            assert!(
+                body.velocity.length() < Fixed::ONE / Fixed::from_int(4),
+                "{body:?}"
+            );
+        }
+        let position = scene.poses.get(ball).unwrap().position;
+        assert!(
+            position.x.abs() < slop() && position.z.abs() < slop(),
+            "{position:?}"
+        );
+    }
+
+    #[test]
+    fn approaching_bodies_touch_and_separate() {
+        let mut scene = Scene::new(Sdf::empty());
+        let floating = |x| Body {
+            velocity: point(x, 0, 0),
+            gravity: Fixed::ZERO,
+            restitution: Fixed::ONE / Fixed::from_int(2),
+            ..Body::new(Fixed::ONE, Fixed::ONE)
+        };
+        let a = scene.ball(point(-2, 0, 0), floating(1));
+        let b = scene.ball(point(2, 0, 0), floating(-1));
+        let gap = |scene: &Scene| {
+            let [a, b] = [a, b].map(|entity| scene.poses.get(entity).unwrap().position);
+            (b - a).length()
+        };
+
+        let mut touched = false;
+        for _ in 0..240 {
+            let contacts = scene.step();
+            touched |= contacts
+                .iter()
+                .any(|contact| contact.a == a && contact.b == Some(b));
+        }
+        assert!(touched);
+        assert!(gap(&scene) > Fixed::from_int(2));
+        let [a, b] = [a, b].map(|entity| scene.bodies.get(entity).unwrap().velocity.x);
+        assert!(a < Fixed::ZERO && b > Fixed::ZERO, "{a:?} {b:?}");
+        assert!(scene.step().is_empty());
+    }
+
+    /// What `runs_hash_the_same_everywhere` gets on every machine and build. Only a change that is
+    /// meant to change how bodies move should change it.
+    const PHYSICS_HASH: u64 = 0xd491_0476_e21f_6587;
+
+    #[test]
+    fn runs_hash_the_same_everywhere() {
+        let mut scene = Scene::new(ground());
+        scene.ball(point(0, 2, 0), Body::new(Fixed::ONE, Fixed::ONE));
+        let spinning = Body {
+            velocity: point(-4, 0, 0),
+            spin: Fixed::from_int(3),
+            ..Body::new(Fixed::from_int(2), Fixed::ONE)
+        };
+        scene.ball(point(2, 1, 0), spinning);
+        let mut touched = false;
+        for _ in 0..300 {
+            touched |= scene.step().iter().any(|contact| contact.b.is_some());
+        }
+        assert!(touched, "the balls should hit each other");
+        assert_eq!(scene.hash(), PHYSICS_HASH, "got {:016x}", scene.hash());
+    }
+}
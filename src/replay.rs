@@ -0,0 +1,268 @@
+//! Recordings of the inputs that drove a simulation, which reproduce a run exactly, for bug
+//! reports and for checking determinism.
+//!
+//! Layout, with all integers little-endian:
+//! - the magic bytes `LSRP` and a `u32` version,
+//! - the `u64` content hash of the scene that was recorded,
+//! - a `u32` tick count, followed by that many ticks, each of which is a `u8` of `InputFrame` bits
+//!   and the `u64` checksum of the simulation after that tick.
+
+use crate::*;
+
+const MAGIC: [u8; 4] = *b"LSRP";
+const VERSION: u32 = 1;
+
+/// The reasons that `Replay::from_bytes` can reject its input.
+#[derive(Debug, PartialEq)]
+pub enum ReplayError {
+    /// The input ended in the middle of a value.
+    UnexpectedEnd,
+    /// The input doesn't start with the magic bytes.
+    BadMagic,
+    /// The input has a version that this build can't read.
+    UnsupportedVersion(u32),
+    /// An input frame had bits for unknown buttons.
+    UnknownButtons {
+        /// The index of the offending tick.
+        tick: usize,
+    },
+    /// There were bytes after the last tick.
+    TrailingBytes,
+}
+
+impl std::fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ReplayError::UnexpectedEnd => write!(f, "unexpected end of input"),
+            ReplayError::BadMagic => write!(f, "input is not a replay"),
+            ReplayError::UnsupportedVersion(version) => {
+                write!(f, "unsupported replay version {version}")
+            }
+            ReplayError::UnknownButtons { tick } => {
+                write!(f, "tick {tick} has inputs for unknown buttons")
+            }
+            ReplayError::TrailingBytes => write!(f, "unexpected bytes after the last tick"),
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+/// One recorded tick.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RecordedTick {
+    /// The inputs that the tick was stepped with.
+    pub input: InputFrame,
+    /// The checksum of the simulation after the tick.
+    pub checksum: u64,
+}
+
+/// The first tick at which a replayed simulation didn't match its recording.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Divergence {
+    /// The number of the tick, counting from 1, so that it is the number of ticks that had run.
+    pub tick: u64,
+    /// The recorded checksum.
+    pub expected: u64,
+    /// The checksum of the replayed simulation.
+    pub actual: u64,
+}
+
+impl std::fmt::Display for Divergence {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "diverged at tick {}: expected checksum {:016x}, got {:016x}",
+            self.tick, self.expected, self.actual
+        )
+    }
+}
+
+/// A recording of the inputs of every tick of a run, along with checksums to check it against.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Replay {
+    /// The content hash of the scene that the run used, from `Sdf::content_hash`.
+    pub scene_hash: u64,
+    /// The ticks of the run, in order.
+    pub ticks: Vec<RecordedTick>,
+}
+
+impl Replay {
+    /// Start an empty recording of a run in the scene with the given content hash.
+    #[must_use]
+    pub fn new(scene_hash: u64) -> Replay {
+        Replay {
+            scene_hash,
+            ticks: vec![],
+        }
+    }
+
+    /// Record a tick that was stepped with `input`, and the checksum of the simulation after it.
+    pub fn record(&mut self, input: InputFrame, checksum: u64) {
+        self.ticks.push(RecordedTick { input, checksum });
+    }
+
+    /// Step a fresh simulation with the recorded inputs, checking its checksum after every tick.
+    /// Returns the first tick that doesn't match.
+    pub fn verify<S: Simulation<Input = InputFrame>>(
+        &self,
+        simulation: &mut S,
+    ) -> Result<(), Divergence> {
+        for (i, recorded) in self.ticks.iter().enumerate() {
+            simulation.step(&recorded.input);
+            let actual = simulation.checksum();
+            if actual != recorded.checksum {
+                return Err(Divergence {
+                    tick: i as u64 + 1,
+                    expected: recorded.checksum,
+                    actual,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Encode this replay into the versioned binary format.
+    #[must_use]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = MAGIC.to_vec();
+        out.extend_from_slice(&VERSION.to_le_bytes());
+        out.extend_from_slice(&self.scene_hash.to_le_bytes());
+        let count = u32::try_from(self.ticks.len()).expect("replay is too long");
+        out.extend_from_slice(&count.to_le_bytes());
+        for tick in &self.ticks {
+            out.push(tick.input.to_bits());
+            out.extend_from_slice(&tick.checksum.to_le_bytes());
+        }
+        out
+    }
+
+    /// Decode a replay from the versioned binary format.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Replay, ReplayError> {
+        let mut r = Reader(bytes);
+        if r.take::<4>()? != MAGIC {
+            return Err(ReplayError::BadMagic);
+        }
+        match r.u32()? {
+            VERSION => {}
+            version => return Err(ReplayError::UnsupportedVersion(version)),
+        }
+        let scene_hash = r.u64()?;
+
+        let mut ticks = vec![];
+        for tick in 0..r.u32()? as usize {
+            let bits = r.u8()?;
+            ticks.push(RecordedTick {
+                input: InputFrame::from_bits(bits).ok_or(ReplayError::UnknownButtons { tick })?,
+                checksum: r.u64()?,
+            });
+        }
+
+        if !r.0.is_empty() {
+            return Err(ReplayError::TrailingBytes);
+        }
+        Ok(Replay { scene_hash, ticks })
+    }
+}
+
+impl From<UnexpectedEnd> for ReplayError {
+    fn from(_: UnexpectedEnd) -> ReplayError {
+        ReplayError::UnexpectedEnd
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A simulation that adds up the bits of its inputs.
+    #[derive(Default)]
+    struct Sum(u64);
+
+    impl Simulation for Sum {
+        type Input = InputFrame;
+        type RenderState = Vec3;
+
+        fn step(&mut self, input: &InputFrame) {
+            self.0 += u64::from(input.to_bits());
+        }
+
+        fn render_state(&self) -> Vec3 {
+            ORIGIN
+        }
+
+        fn hash_state(&self, hasher: &mut StableHasher) {
+            hasher.system("sum", &self.0);
+        }
+    }
+
+    /// Record ten ticks of `Sum`.
+    fn recording() -> Replay {
+        let mut replay = Replay::new(0x0123_4567_89ab_cdef);
+        let mut sum = Sum::default();
+        for i in 0..10 {
+            let input = InputFrame::from_bits(i % (1 << Button::ALL.len())).unwrap();
+            sum.step(&input);
+            replay.record(input, sum.checksum());
+        }
+        replay
+    }
+
+    #[test]
+    fn round_trips() {
+        let replay = recording();
+        assert_eq!(Replay::from_bytes(&replay.to_bytes()), Ok(replay));
+    }
+
+    #[test]
+    fn rejects_truncated_and_corrupted_input() {
+        let bytes = recording().to_bytes();
+        for len in 0..bytes.len() {
+            assert_eq!(
+                Replay::from_bytes(&bytes[..len]),
+                Err(ReplayError::UnexpectedEnd),
+                "{len} bytes"
+            );
+        }
+
+        let corrupt = |i: usize, byte: u8| {
+            let mut bytes = bytes.clone();
+            bytes[i] = byte;
+            Replay::from_bytes(&bytes)
+        };
+        assert_eq!(corrupt(0, b'X'), Err(ReplayError::BadMagic));
+        assert_eq!(corrupt(4, 9), Err(ReplayError::UnsupportedVersion(9)));
+        // the header, the scene hash, the tick count, and then tick 0's input
+        assert_eq!(
+            corrupt(4 + 4 + 8 + 4, 0xff),
+            Err(ReplayError::UnknownButtons { tick: 0 })
+        );
+
+        let mut long = bytes.clone();
+        long.push(0);
+        assert_eq!(Replay::from_bytes(&long), Err(ReplayError::TrailingBytes));
+    }
+
+    #[test]
+    fn verify_reports_the_first_divergence() {
+        let mut replay = recording();
+        assert_eq!(replay.verify(&mut Sum::default()), Ok(()));
+
+        let expected = [3, 6].map(|i| {
+            replay.ticks[i].checksum ^= 1;
+            replay.ticks[i].checksum
+        });
+        let mut sum = Sum::default();
+        for tick in &replay.ticks[..4] {
+            sum.step(&tick.input);
+        }
+        assert_eq!(
+            replay.verify(&mut Sum::default()),
+            Err(Divergence {
+                tick: 4,
+                expected: expected[0],
+                actual: sum.checksum(),
+            })
+        );
+    }
+}
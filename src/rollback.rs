@@ -0,0 +1,226 @@
+//! Rollback on top of a lockstep session, which hides latency without input delay.
+//!
+//! Instead of waiting for the other peer's inputs, the simulation runs ahead with predicted ones,
+//! which repeat the last inputs that arrived, and snapshots its state before every tick. When the
+//! real inputs arrive, and they aren't what was predicted, it rolls back to the snapshot before the
+//! first wrong tick and re-simulates from there. The peers still compare checksums, but only of
+//! confirmed ticks, which both have simulated with the real inputs.
+
+use crate::*;
+use std::collections::VecDeque;
+
+/// The state of the simulation before a tick that hasn't been confirmed yet, and the inputs that
+/// it was stepped with, which may be predicted.
+struct Snapshot<S> {
+    state: S,
+    inputs: [InputFrame; PLAYERS],
+}
+
+/// One peer's side of a rollback session.
+///
+/// Every tick, the game adds the local input with `add_local_input`, which steps the simulation
+/// with a prediction of the other player's input, and calls `poll` to exchange packets, which
+/// rolls back if the prediction was wrong.
+pub struct Rollback<S, T: Transport> {
+    session: Session<T>,
+    simulation: S,
+    history: VecDeque<Snapshot<S>>, // from the first unconfirmed tick to the current one
+    log: DesyncLog,
+    rollbacks: u64,
+    resimulated: u64,
+}
+
+impl<S, T> Rollback<S, T>
+where
+    S: Simulation<Input = [InputFrame; PLAYERS]> + Clone,
+    T: Transport,
+{
+    /// Start a session at tick zero as player `local`, with local inputs taking effect `delay`
+    /// ticks after they are added, and predicting up to `window` ticks ahead of the confirmed
+    /// ones. After that, it stalls like a lockstep session.
+    pub fn new(transport: T, local: usize, delay: u64, window: u64, simulation: S) -> Self {
+        Rollback {
+            session: Session::new(transport, local, delay).with_prediction_window(window),
+            simulation,
+            history: VecDeque::new(),
+            log: DesyncLog::new(),
+            rollbacks: 0,
+            resimulated: 0,
+        }
+    }
+
+    /// Get the lockstep session that exchanges the inputs.
+    pub fn session(&self) -> &Session<T> {
+        &self.session
+    }
+
+    /// Get the hashes and dumps of the confirmed ticks, to explain a desync with.
+    pub fn desync_log(&self) -> &DesyncLog {
+        &self.log
+    }
+
+    /// Get the simulation, which may be using predicted inputs.
+    pub fn simulation(&self) -> &S {
+        &self.simulation
+    }
+
+    /// Get the number of ticks that the simulation has run, including predicted ones.
+    #[must_use]
+    pub fn tick(&self) -> u64 {
+        self.session.tick() + self.history.len() as u64
+    }
+
+    /// Get the number of times that a prediction was wrong.
+    #[must_use]
+    pub fn rollbacks(&self) -> u64 {
+        self.rollbacks
+    }
+
+    /// Get the number of ticks that were simulated again after rolling back.
+    #[must_use]
+    pub fn resimulated(&self) -> u64 {
+        self.resimulated
+    }
+
+    /// Guess the other player's input, by repeating the last one that arrived.
+    fn predict(&self) -> InputFrame {
+        let remote = self.session.inputs(self.session.remote());
+        remote.last().copied().unwrap_or_default()
+    }
+
+    /// Queue the local player's input for the tick `delay` ticks from now, and step the simulation
+    /// if that makes a tick due. Returns false, and drops the input, if the session is stalled
+    /// because the prediction window is full.
+    pub fn add_local_input(&mut self, input: InputFrame) -> bool {
+        if !self.session.add_local_input(input) {
+            return false;
+        }
+        let tick = self.tick();
+        let local = self.session.inputs(self.session.local());
+        if local.len() as u64 > tick + self.session.delay() {
+            let mut inputs = [self.predict(); PLAYERS];
+            inputs[self.session.local()] = local[tick as usize];
+            let remote = self.session.inputs(self.session.remote());
+            if let Some(&input) = remote.get(tick as usize) {
+                inputs[self.session.remote()] = input;
+            }
+            self.history.push_back(Snapshot {
+                state: self.simulation.clone(),
+                inputs,
+            });
+            self.simulation.step(&inputs);
+        }
+        true
+    }
+
+    /// Exchange packets with the other peer, and roll back if any of the inputs that arrived
+    /// weren't what was predicted.
+    pub fn poll(&mut self) -> Result<(), NetError> {
+        self.session.poll()?;
+
+        // the session returns the ticks that both players' inputs have arrived for
+        let first = self.session.tick();
+        let mut wrong = None;
+        while let Some(inputs) = self.session.next_inputs() {
+            let i = (self.session.tick() - 1 - first) as usize;
+            if self.history[i].inputs != inputs {
+                self.history[i].inputs = inputs;
+                wrong.get_or_insert(i);
+            }
+        }
+        let confirmed = (self.session.tick() - first) as usize;
+
+        if let Some(wrong) = wrong {
+            self.rollbacks += 1;
+            let prediction = self.predict();
+            let remote = self.session.remote();
+            self.simulation = self.history[wrong].state.clone();
+            for i in wrong..self.history.len() {
+                if i >= confirmed {
+                    self.history[i].inputs[remote] = prediction;
+                }
+                self.history[i].state = self.simulation.clone();
+                self.simulation.step(&self.history[i].inputs);
+                self.resimulated += 1;
+            }
+        }
+
+        // the state after each confirmed tick is the snapshot before the next one
+        for i in 1..=confirmed {
+            let tick = first + i as u64;
+            let state = self.history.get(i).map_or(&self.simulation, |s| &s.state);
+            let checksum = self.log.record(tick, state);
+            self.session.report_checksum(tick, checksum)?;
+        }
+        self.history.drain(..confirmed);
+        Ok(())
+    }
+}
+
+/// Checks that a simulation is deterministic, by rolling back the last few ticks after every tick
+/// and re-simulating them, like a rollback session would after a wrong prediction, and checking
+/// that the checksums come out the same. This catches state that a snapshot misses, and anything
+/// else that makes stepping from the same state with the same inputs give different results.
+pub struct SyncTest<S: Simulation> {
+    simulation: S,
+    history: VecDeque<(S, S::Input, u64)>, // the state before each tick, its inputs and checksum
+    distance: usize,
+    tick: u64,
+}
+
+impl<S> SyncTest<S>
+where
+    S: Simulation + Clone,
+    S::Input: Clone,
+{
+    /// Start testing a simulation, rolling back `distance` ticks every tick.
+    pub fn new(simulation: S, distance: usize) -> SyncTest<S> {
+        SyncTest {
+            simulation,
+            history: VecDeque::new(),
+            distance: distance.max(1),
+            tick: 0,
+        }
+    }
+
+    /// Get the simulation.
+    pub fn simulation(&self) -> &S {
+        &self.simulation
+    }
+
+    /// Get the number of ticks that have run.
+    #[must_use]
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    /// Step the simulation, and then roll back and re-simulate the last few ticks. Returns the
+    /// first tick that didn't match the first time that it was simulated.
+    pub fn step(&mut self, inputs: &S::Input) -> Result<(), Divergence> {
+        let before = self.simulation.clone();
+        self.simulation.step(inputs);
+        self.tick += 1;
+        self.history
+            .push_back((before, inputs.clone(), self.simulation.checksum()));
+        if self.history.len() > self.distance {
+            self.history.pop_front();
+        }
+
+        // carry on from the re-simulated state, so that the test covers it
+        let first_tick = self.tick - self.history.len() as u64;
+        self.simulation = self.history[0].0.clone();
+        for (i, (state, inputs, expected)) in self.history.iter_mut().enumerate() {
+            *state = self.simulation.clone();
+            self.simulation.step(inputs);
+            let actual = self.simulation.checksum();
+            if actual != *expected {
+                return Err(Divergence {
+                    tick: first_tick + i as u64 + 1,
+                    expected: *expected,
+                    actual,
+                });
+            }
+        }
+        Ok(())
+    }
+}
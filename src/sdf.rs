@@ -1,75 +1,489 @@
 use crate::*;
 
+mod dump;
+mod edit;
+mod encode;
+mod intern;
+
+pub use edit::NodeId;
+pub use encode::DecodeError;
+pub(crate) use encode::{fnv1a, Reader, UnexpectedEnd};
+use intern::Interner;
+use std::ops::*;
+
 /// An `Sdf` represents a Constructive Solid Geometry DAG.
+// Invariant: there is always at least one `Distance`, and the last one is the root.
+#[derive(Clone)]
 #[must_use]
 pub struct Sdf {
     distances: Vec<Distance>,
     materials: Vec<Material>,
+    names: Vec<(String, usize)>, // the names of nodes, in the order that they were given
 }
 
+/// Identifies an instance, so that its transformation can be changed after construction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct InstanceId(pub u32);
+
 /// A node in an `Sdf` DAG.
 // Invariant: `Distance`s should only point to `Distance`s that have a strictly smaller index.
 // This both ensures that the graph is a DAG, and makes distance computations simpler.
-#[derive(PartialEq)]
+#[derive(Clone, PartialEq)]
 enum Distance {
     // primitives
     Sphere {
         center: Vec3,
-        radius: f32,
+        radius: Param,
         material: usize,
     },
     Plane {
         normal: Vec3, // must be normalized
-        offset: f32,
+        offset: Param,
         material: usize,
     },
+    Ocean {
+        origin: Vec3,
+        waves: Vec<Wave>, // directions must be normalized and horizontal
+        material: usize,
+    },
+    Empty,      // infinitely far from every point
+    Everything, // infinitely deep inside every point
     // operations
     Union(usize, usize),
     Intersection(usize, usize),
     Exclusion(usize, usize),
     Subtraction(usize, usize),
+    // transformations
+    Transform {
+        child: usize,
+        inverse_transform: Transform, // applied to the point before evaluating `child`
+        instance: Option<InstanceId>,
+    },
+    Repeat {
+        child: usize,
+        period: Vec3,             // a zero component means no repetition along that axis
+        counts: Option<[u32; 3]>, // the number of copies along each axis, if limited
+    },
+    Mirror {
+        child: usize,
+        normal: Vec3, // must be normalized
+        offset: Param,
+    },
+    Symmetric {
+        child: usize,
+        axes: [bool; 3],
+    },
+    // deformations
+    Twist {
+        child: usize,
+        axis: Vec3,  // must be normalized
+        along: Vec3, // must be normalized; a twist if equal to `axis`, a bend if perpendicular
+        rate: Param, // radians per unit along `along`
+    },
+    Taper {
+        child: usize,
+        axis: Vec3, // must be normalized
+        factor: Param,
+    },
+    Rotate {
+        child: usize,
+        axis: Vec3, // must be normalized
+        angle: Param,
+    },
+    Elongate {
+        child: usize,
+        extent: Vec3,
+    },
+    // modifiers
+    Offset {
+        child: usize,
+        amount: Param,
+    },
+    Shell {
+        child: usize,
+        thickness: Param,
+    },
+    Displace {
+        child: usize,
+        noise: Noise,
+        amplitude: Param,
+        frequency: f32,
+        octaves: u32,
+    },
+}
+
+impl Distance {
+    /// Get the material that this node points at, if any.
+    fn material(&self) -> Option<usize> {
+        match self {
+            Distance::Sphere { material, .. }
+            | Distance::Plane { material, .. }
+            | Distance::Ocean { material, .. } => Some(*material),
+            Distance::Empty
+            | Distance::Everything
+            | Distance::Union(..)
+            | Distance::Intersection(..)
+            | Distance::Exclusion(..)
+            | Distance::Subtraction(..)
+            | Distance::Transform { .. }
+            | Distance::Repeat { .. }
+            | Distance::Mirror { .. }
+            | Distance::Symmetric { .. }
+            | Distance::Twist { .. }
+            | Distance::Taper { .. }
+            | Distance::Rotate { .. }
+            | Distance::Elongate { .. }
+            | Distance::Offset { .. }
+            | Distance::Shell { .. }
+            | Distance::Displace { .. } => None,
+        }
+    }
+
+    /// Get the nodes that this node points at, in order.
+    fn children(&self) -> Vec<usize> {
+        match self {
+            Distance::Sphere { .. }
+            | Distance::Plane { .. }
+            | Distance::Ocean { .. }
+            | Distance::Empty
+            | Distance::Everything => vec![],
+            Distance::Union(x, y)
+            | Distance::Intersection(x, y)
+            | Distance::Exclusion(x, y)
+            | Distance::Subtraction(x, y) => vec![*x, *y],
+            Distance::Transform { child, .. }
+            | Distance::Repeat { child, .. }
+            | Distance::Mirror { child, .. }
+            | Distance::Symmetric { child, .. }
+            | Distance::Twist { child, .. }
+            | Distance::Taper { child, .. }
+            | Distance::Rotate { child, .. }
+            | Distance::Elongate { child, .. }
+            | Distance::Offset { child, .. }
+            | Distance::Shell { child, .. }
+            | Distance::Displace { child, .. } => vec![*child],
+        }
+    }
+
+    /// Replace the material that this node points at, if any.
+    fn map_material(&mut self, f: impl Fn(usize) -> usize) {
+        match self {
+            Distance::Sphere { material, .. }
+            | Distance::Plane { material, .. }
+            | Distance::Ocean { material, .. } => {
+                *material = f(*material);
+            }
+            Distance::Empty
+            | Distance::Everything
+            | Distance::Union(..)
+            | Distance::Intersection(..)
+            | Distance::Exclusion(..)
+            | Distance::Subtraction(..)
+            | Distance::Transform { .. }
+            | Distance::Repeat { .. }
+            | Distance::Mirror { .. }
+            | Distance::Symmetric { .. }
+            | Distance::Twist { .. }
+            | Distance::Taper { .. }
+            | Distance::Rotate { .. }
+            | Distance::Elongate { .. }
+            | Distance::Offset { .. }
+            | Distance::Shell { .. }
+            | Distance::Displace { .. } => {}
+        }
+    }
+
+    /// Replace the nodes that this node points at.
+    fn map_children(&mut self, f: impl Fn(usize) -> usize) {
+        match self {
+            Distance::Sphere { .. }
+            | Distance::Plane { .. }
+            | Distance::Ocean { .. }
+            | Distance::Empty
+            | Distance::Everything => {}
+            Distance::Union(x, y)
+            | Distance::Intersection(x, y)
+            | Distance::Exclusion(x, y)
+            | Distance::Subtraction(x, y) => {
+                *x = f(*x);
+                *y = f(*y);
+            }
+            Distance::Transform { child, .. }
+            | Distance::Repeat { child, .. }
+            | Distance::Mirror { child, .. }
+            | Distance::Symmetric { child, .. }
+            | Distance::Twist { child, .. }
+            | Distance::Taper { child, .. }
+            | Distance::Rotate { child, .. }
+            | Distance::Elongate { child, .. }
+            | Distance::Offset { child, .. }
+            | Distance::Shell { child, .. }
+            | Distance::Displace { child, .. } => *child = f(*child),
+        }
+    }
+
+    /// Check whether this node evaluates its children at a different point than its own.
+    fn moves_point(&self) -> bool {
+        match self {
+            Distance::Sphere { .. }
+            | Distance::Plane { .. }
+            | Distance::Ocean { .. }
+            | Distance::Empty
+            | Distance::Everything
+            | Distance::Union(..)
+            | Distance::Intersection(..)
+            | Distance::Exclusion(..)
+            | Distance::Subtraction(..)
+            | Distance::Offset { .. }
+            | Distance::Shell { .. }
+            | Distance::Displace { .. } => false,
+            Distance::Transform { .. }
+            | Distance::Repeat { .. }
+            | Distance::Mirror { .. }
+            | Distance::Symmetric { .. }
+            | Distance::Twist { .. }
+            | Distance::Taper { .. }
+            | Distance::Rotate { .. }
+            | Distance::Elongate { .. } => true,
+        }
+    }
+
+    /// Get a bound on how much this node can stretch distances between the points that its
+    /// children are evaluated at, relative to distances between the points that it is evaluated
+    /// at. Deformations are only bounded near their axis, so these bounds assume that points are
+    /// within `MAX_DIST` of it. Primitives whose distances are only estimates report their own bound.
+    /// Animated parameters are evaluated at time `t`.
+    fn lipschitz(&self, t: f32) -> f32 {
+        match self {
+            Distance::Ocean { waves, .. } => OceanBounds::new(waves).lipschitz,
+            Distance::Twist { rate, .. } => 1.0 + rate.at(t).abs() * MAX_DIST,
+            Distance::Taper { factor, .. } => {
+                let factor = factor.at(t);
+                let min_scale = (1.0 - factor.abs() * MAX_DIST).max(MIN_TAPER_SCALE);
+                min_scale.recip().max(1.0) + factor.abs() * MAX_DIST / (min_scale * min_scale)
+            }
+            // each octave doubles the frequency and halves the amplitude, so they contribute equally
+            Distance::Displace {
+                amplitude,
+                frequency,
+                octaves,
+                ..
+            } => 1.0 + (amplitude.at(t) * frequency).abs() * Noise::LIPSCHITZ * *octaves as f32,
+            Distance::Sphere { .. }
+            | Distance::Plane { .. }
+            | Distance::Empty
+            | Distance::Everything
+            | Distance::Union(..)
+            | Distance::Intersection(..)
+            | Distance::Exclusion(..)
+            | Distance::Subtraction(..)
+            | Distance::Transform { .. }
+            | Distance::Repeat { .. }
+            | Distance::Mirror { .. }
+            | Distance::Symmetric { .. }
+            | Distance::Rotate { .. }
+            | Distance::Elongate { .. }
+            | Distance::Offset { .. }
+            | Distance::Shell { .. } => 1.0,
+        }
+    }
+}
+
+/// What a node is equivalent to once the `Empty` and `Everything` nodes below it are folded away.
+enum Folded {
+    Node(usize),
+    Empty,
+    Everything,
+}
+
+impl Distance {
+    /// Find a simpler node that this node is equivalent to, because some of its children are
+    /// `Empty` or `Everything`. Children are looked up in `nodes`.
+    fn fold(&self, nodes: &[Distance]) -> Option<Folded> {
+        // whether a node is everything, if it is either constant
+        let constant = |node: usize| match nodes[node] {
+            Distance::Empty => Some(false),
+            Distance::Everything => Some(true),
+            _ => None,
+        };
+        match *self {
+            Distance::Union(x, y) => match (constant(x), constant(y)) {
+                (Some(true), _) | (_, Some(true)) => Some(Folded::Everything),
+                (Some(false), _) => Some(Folded::Node(y)),
+                (_, Some(false)) => Some(Folded::Node(x)),
+                (None, None) => None,
+            },
+            Distance::Intersection(x, y) => match (constant(x), constant(y)) {
+                (Some(false), _) | (_, Some(false)) => Some(Folded::Empty),
+                (Some(true), _) => Some(Folded::Node(y)),
+                (_, Some(true)) => Some(Folded::Node(x)),
+                (None, None) => None,
+            },
+            Distance::Exclusion(x, y) => match (constant(x), constant(y)) {
+                (Some(false), _) => Some(Folded::Node(y)),
+                (_, Some(false)) => Some(Folded::Node(x)),
+                (Some(true), Some(true)) => Some(Folded::Empty),
+                _ => None,
+            },
+            // `y` without `x`; removing `x` from everything has no node to fold into
+            Distance::Subtraction(x, y) => match (constant(x), constant(y)) {
+                (Some(true), _) | (_, Some(false)) => Some(Folded::Empty),
+                (Some(false), _) => Some(Folded::Node(y)),
+                _ => None,
+            },
+            // the surface of a constant is infinitely far away on both sides
+            Distance::Shell { child, .. } => constant(child).map(|_| Folded::Empty),
+            _ => match self.children()[..] {
+                [child] => constant(child).map(|everything| {
+                    if everything {
+                        Folded::Everything
+                    } else {
+                        Folded::Empty
+                    }
+                }),
+                _ => None,
+            },
+        }
+    }
+}
+
+/// The smallest amount that `Sdf::taper` can shrink cross-sections by, to avoid dividing by zero.
+const MIN_TAPER_SCALE: f32 = 0.1; // assert: equal to `MIN_TAPER_SCALE` in `shader.wgsl`
+
+impl Distance {
+    /// Get the point that the only child of a deformation node should be evaluated at, at time `t`.
+    fn deform<S: Scalar>(&self, p: Vector3<S>, t: S) -> Vector3<S> {
+        match self {
+            Distance::Twist {
+                axis, along, rate, ..
+            } => rotate(p, *axis, -rate.at(t) * p.dot(Vector3::from_f32(*along))),
+            Distance::Taper { axis, factor, .. } => {
+                let axis = Vector3::from_f32(*axis);
+                let h = p.dot(axis);
+                let scale = (S::ONE + factor.at(t) * h).max(S::from_f32(MIN_TAPER_SCALE));
+                axis * h + (p - axis * h) / scale
+            }
+            Distance::Rotate { axis, angle, .. } => rotate(p, *axis, -angle.at(t)),
+            Distance::Elongate { extent, .. } => {
+                p - p.binary(Vector3::from_f32(*extent), |x, e| x.clamp(-e, e))
+            }
+            _ => unreachable!("not a deformation"),
+        }
+    }
+}
+
+/// Rotate a point counter-clockwise around a unit axis through the origin, by an angle in radians.
+fn rotate<S: Scalar>(p: Vector3<S>, axis: Vec3, angle: S) -> Vector3<S> {
+    let k = Vector3::from_f32(axis);
+    let (sin, cos) = angle.sin_cos();
+    p * cos + k.cross(p) * sin + k * (k.dot(p) * (S::ONE - cos))
 }
 
 impl Sdf {
+    /// Deduplicate equal nodes and materials, fold away `Empty` and `Everything` nodes, and remove
+    /// the nodes and materials that the root can't reach. This runs in expected linear time by
+    /// hash-consing; see the `intern` module for how floats are compared. The first occurrence of
+    /// each node is kept, so the output is in the same order.
     fn simplify(self) -> Sdf {
-        let mut materials = vec![];
-        let mut material_map: HashMap<usize, usize> = HashMap::new();
-        for (i, material) in self.materials.into_iter().enumerate() {
-            if let Some(j) = materials.iter().position(|x| *x == material) {
-                material_map.insert(i, j);
-            } else {
-                material_map.insert(i, materials.len());
-                materials.push(material);
-            }
+        let mut materials = Interner::new();
+        let material_map: Vec<usize> = self
+            .materials
+            .into_iter()
+            .map(|material| materials.intern(material))
+            .collect();
+
+        let mut named = vec![false; self.distances.len()];
+        for (_, node) in &self.names {
+            named[*node] = true;
         }
 
-        let mut distances = vec![];
-        let mut distance_map: HashMap<usize, usize> = HashMap::new();
+        let mut distances = Interner::new();
+        let mut distance_map: Vec<usize> = Vec::with_capacity(self.distances.len());
         for (i, mut distance) in self.distances.into_iter().enumerate() {
-            match &mut distance {
-                Distance::Sphere { material, .. } | Distance::Plane { material, .. } => {
-                    *material = material_map[material];
+            distance.map_material(|material| material_map[material]);
+            distance.map_children(|child| distance_map[child]);
+            if !named[i] {
+                match distance.fold(&distances.values) {
+                    Some(Folded::Node(node)) => {
+                        distance_map.push(node);
+                        continue;
+                    }
+                    Some(Folded::Empty) => distance = Distance::Empty,
+                    Some(Folded::Everything) => distance = Distance::Everything,
+                    None => {}
                 }
-                Distance::Union(x, y)
-                | Distance::Intersection(x, y)
-                | Distance::Exclusion(x, y)
-                | Distance::Subtraction(x, y) => {
-                    *x = distance_map[x];
-                    *y = distance_map[y];
+            }
+            distance_map.push(if named[i] {
+                distances.push(distance)
+            } else {
+                distances.intern(distance)
+            });
+        }
+
+        let root = *distance_map.last().expect("sdf has no distances");
+        Sdf {
+            distances: distances.values,
+            materials: materials.values,
+            names: self
+                .names
+                .into_iter()
+                .map(|(name, node)| (name, distance_map[node]))
+                .collect(),
+        }
+        .without_dead_nodes(root)
+    }
+
+    /// Remove the nodes that are not reachable from `root`, and the materials that they used.
+    /// Since children always have smaller indices than their parents, `root` becomes the last node.
+    fn without_dead_nodes(self, root: usize) -> Sdf {
+        let mut alive = vec![false; root + 1];
+        alive[root] = true;
+        for i in (0..=root).rev() {
+            if alive[i] {
+                for child in self.distances[i].children() {
+                    alive[child] = true;
                 }
             }
+        }
 
-            if let Some(j) = distances.iter().position(|x| *x == distance) {
-                distance_map.insert(i, j);
-            } else {
-                distance_map.insert(i, distances.len());
+        let mut used = vec![false; self.materials.len()];
+        for (distance, _) in self.distances.iter().zip(&alive).filter(|(_, a)| **a) {
+            if let Some(material) = distance.material() {
+                used[material] = true;
+            }
+        }
+
+        let mut material_map = vec![usize::MAX; self.materials.len()];
+        let mut materials = vec![];
+        for (i, material) in self.materials.into_iter().enumerate() {
+            if used[i] {
+                material_map[i] = materials.len();
+                materials.push(material);
+            }
+        }
+
+        let mut distance_map = vec![usize::MAX; alive.len()];
+        let mut distances = vec![];
+        for (i, mut distance) in self.distances.into_iter().take(root + 1).enumerate() {
+            if alive[i] {
+                distance.map_material(|material| material_map[material]);
+                distance.map_children(|child| distance_map[child]);
+                distance_map[i] = distances.len();
                 distances.push(distance);
             }
         }
 
+        let names = self
+            .names
+            .into_iter()
+            .filter(|(_, node)| *node <= root && alive[*node])
+            .map(|(name, node)| (name, distance_map[node]))
+            .collect();
+
         Sdf {
             distances,
             materials,
+            names,
         }
     }
 
@@ -78,20 +492,13 @@ impl Sdf {
         let material_offset = self.materials.len();
 
         self.materials.append(&mut other.materials);
+        for (name, node) in other.names {
+            self.names.push((name, node + distance_offset));
+        }
 
         for mut distance in other.distances {
-            match &mut distance {
-                Distance::Sphere { material, .. } | Distance::Plane { material, .. } => {
-                    *material += material_offset;
-                }
-                Distance::Union(x, y)
-                | Distance::Intersection(x, y)
-                | Distance::Exclusion(x, y)
-                | Distance::Subtraction(x, y) => {
-                    *x += distance_offset;
-                    *y += distance_offset;
-                }
-            }
+            distance.map_material(|material| material + material_offset);
+            distance.map_children(|child| child + distance_offset);
             self.distances.push(distance);
         }
 
@@ -99,30 +506,81 @@ impl Sdf {
     }
 
     /// Construct a new SDF of a sphere, centered at the origin.
-    pub fn sphere(radius: f32, material: Material) -> Sdf {
+    pub fn sphere(radius: impl Into<Param>, material: Material) -> Sdf {
         Sdf {
             distances: vec![Distance::Sphere {
                 center: ORIGIN,
-                radius,
+                radius: radius.into(),
                 material: 0,
             }],
             materials: vec![material],
+            names: vec![],
         }
     }
 
-    /// Construct a new SDF of a cube, centered at the origin.
-    pub fn plane(normal: Vec3, offset: f32, material: Material) -> Sdf {
+    /// Construct a new SDF of the half-space behind a plane, which is `offset` along its normal
+    /// from the origin. The normal doesn't have to be normalized.
+    pub fn plane(normal: Vec3, offset: impl Into<Param>, material: Material) -> Sdf {
         Sdf {
             distances: vec![Distance::Plane {
-                normal,
-                offset,
+                normal: normal.normalized(),
+                offset: offset.into(),
                 material: 0,
             }],
             materials: vec![material],
+            names: vec![],
+        }
+    }
+
+    /// Construct a new SDF of an ocean whose undisturbed surface is the horizontal plane through
+    /// the origin, with y pointing up, disturbed by a sum of Gerstner waves.
+    /// The waves move with time, so they are only animated by `distance_at_time` and the shader.
+    pub fn ocean(waves: &[Wave], material: Material) -> Sdf {
+        Sdf {
+            distances: vec![Distance::Ocean {
+                origin: ORIGIN,
+                waves: horizontal_waves(waves),
+                material: 0,
+            }],
+            materials: vec![material],
+            names: vec![],
+        }
+    }
+
+    /// Construct an SDF that contains nothing, whose distance is infinite everywhere.
+    /// It is the identity of `union`, so it is a natural starting point for building up a scene.
+    pub fn empty() -> Sdf {
+        Sdf {
+            distances: vec![Distance::Empty],
+            materials: vec![],
+            names: vec![],
         }
     }
 
+    /// Construct an SDF that contains everything, whose distance is negative infinity everywhere.
+    /// It is the identity of `intersect`.
+    pub fn everything() -> Sdf {
+        Sdf {
+            distances: vec![Distance::Everything],
+            materials: vec![],
+            names: vec![],
+        }
+    }
+
+    /// Check whether this SDF contains nothing, as far as its construction can tell.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        matches!(self.distances.last(), Some(Distance::Empty))
+    }
+
+    /// Check whether this SDF contains everything, as far as its construction can tell.
+    #[must_use]
+    pub fn is_everything(&self) -> bool {
+        matches!(self.distances.last(), Some(Distance::Everything))
+    }
+
     /// Union another SDF into this one.
+    /// Unions with `empty` and `everything` are folded away, as are the other operations'.
     pub fn union(self, other: Sdf) -> Sdf {
         let self_root = self.distances.len() - 1;
         let mut out = self.append(other);
@@ -149,7 +607,8 @@ impl Sdf {
         out.simplify()
     }
 
-    /// Subtract another SDF from this one.
+    /// Subtract this SDF from another one: the result is the points in `other` that are not in
+    /// `self`.
     pub fn subtract(self, other: Sdf) -> Sdf {
         let self_root = self.distances.len() - 1;
         let mut out = self.append(other);
@@ -158,28 +617,484 @@ impl Sdf {
         out.simplify()
     }
 
+    /// Union any number of SDFs. The result is `empty` if there are none.
+    /// The unions form a balanced tree, so it is only logarithmically deep.
+    pub fn union_all(sdfs: impl IntoIterator<Item = Sdf>) -> Sdf {
+        Sdf::balanced(sdfs, Sdf::union).unwrap_or_else(Sdf::empty)
+    }
+
+    /// Intersect any number of SDFs. The result is `everything` if there are none.
+    /// The intersections form a balanced tree, so it is only logarithmically deep.
+    pub fn intersect_all(sdfs: impl IntoIterator<Item = Sdf>) -> Sdf {
+        Sdf::balanced(sdfs, Sdf::intersect).unwrap_or_else(Sdf::everything)
+    }
+
+    /// Combine SDFs pairwise with `combine` until one is left, keeping their order.
+    fn balanced(sdfs: impl IntoIterator<Item = Sdf>, combine: fn(Sdf, Sdf) -> Sdf) -> Option<Sdf> {
+        let mut level: Vec<Sdf> = sdfs.into_iter().collect();
+        while level.len() > 1 {
+            let mut pairs = level.into_iter();
+            level = vec![];
+            while let Some(first) = pairs.next() {
+                level.push(match pairs.next() {
+                    Some(second) => combine(first, second),
+                    None => first,
+                });
+            }
+        }
+        level.pop()
+    }
+
     /// Translate this SDF along a vector.
+    /// Primitives are moved in place, unless some node moves the point that its children are
+    /// evaluated at, in which case the whole SDF is wrapped in a transformation instead.
     pub fn translate(mut self, vec: Vec3) -> Sdf {
+        if self.distances.iter().any(Distance::moves_point) {
+            return self.transform(Transform::translation(vec));
+        }
         for distance in &mut self.distances {
             match distance {
                 Distance::Sphere { center, .. } => {
-                    *center = *center + vec;
+                    *center += vec;
                 }
                 Distance::Plane { normal, offset, .. } => {
-                    *offset += vec.dot(*normal);
+                    offset.base += vec.dot(*normal);
+                }
+                Distance::Ocean { origin, .. } => {
+                    *origin += vec;
                 }
-                Distance::Union(..)
+                Distance::Empty
+                | Distance::Everything
+                | Distance::Union(..)
                 | Distance::Intersection(..)
                 | Distance::Exclusion(..)
-                | Distance::Subtraction(..) => {}
+                | Distance::Subtraction(..)
+                | Distance::Transform { .. }
+                | Distance::Repeat { .. }
+                | Distance::Mirror { .. }
+                | Distance::Symmetric { .. }
+                | Distance::Twist { .. }
+                | Distance::Taper { .. }
+                | Distance::Rotate { .. }
+                | Distance::Elongate { .. }
+                | Distance::Offset { .. }
+                | Distance::Shell { .. }
+                | Distance::Displace { .. } => {}
             }
         }
         self
     }
+
+    /// Place this SDF with a similarity transformation, which may scale it uniformly.
+    /// Unlike `translate`, this shares the nodes of this SDF with any other copies of it.
+    pub fn transform(self, transform: Transform) -> Sdf {
+        self.wrap_in_transform(transform, None)
+    }
+
+    /// Scale this SDF uniformly away from the origin by a positive factor.
+    pub fn scale(self, factor: f32) -> Sdf {
+        self.transform(Transform::scaling(factor))
+    }
+
+    /// Place this SDF with a similarity transformation, as an instance that can be moved later
+    /// with `set_instance_transform`. Instances with different ids are never merged.
+    pub fn instance(self, id: InstanceId, transform: Transform) -> Sdf {
+        self.wrap_in_transform(transform, Some(id))
+    }
+
+    fn wrap_in_transform(mut self, transform: Transform, instance: Option<InstanceId>) -> Sdf {
+        self.distances.push(Distance::Transform {
+            child: self.distances.len() - 1,
+            inverse_transform: transform.inverse(),
+            instance,
+        });
+        self.simplify()
+    }
+
+    /// Tile this SDF infinitely, with copies at every integer multiple of `period`.
+    /// A zero component of `period` means that there is no repetition along that axis.
+    /// The distance stays conservative as long as this SDF fits within one period of its cell.
+    pub fn repeat(self, period: Vec3) -> Sdf {
+        self.wrap_in_repeat(period, None)
+    }
+
+    /// Tile this SDF `counts` times along each axis, starting at the origin and continuing in
+    /// the positive direction. Otherwise the same as `repeat`.
+    pub fn repeat_limited(self, period: Vec3, counts: [u32; 3]) -> Sdf {
+        assert!(
+            counts.iter().all(|count| *count > 0),
+            "counts must be positive"
+        );
+        self.wrap_in_repeat(period, Some(counts))
+    }
+
+    fn wrap_in_repeat(mut self, period: Vec3, counts: Option<[u32; 3]>) -> Sdf {
+        self.distances.push(Distance::Repeat {
+            child: self.distances.len() - 1,
+            period,
+            counts,
+        });
+        self.simplify()
+    }
+
+    /// Reflect the half-space behind a plane onto the half-space in front of it, so that only the
+    /// part of this SDF in front of the plane is kept and mirrored.
+    /// The plane is the same as in `Sdf::plane`.
+    pub fn mirror(mut self, normal: Vec3, offset: impl Into<Param>) -> Sdf {
+        self.distances.push(Distance::Mirror {
+            child: self.distances.len() - 1,
+            normal: normal.normalized(),
+            offset: offset.into(),
+        });
+        self.simplify()
+    }
+
+    /// Mirror this SDF across the coordinate planes of each of the given axes, keeping only the
+    /// part with positive coordinates along those axes.
+    pub fn symmetric(mut self, axes: [bool; 3]) -> Sdf {
+        self.distances.push(Distance::Symmetric {
+            child: self.distances.len() - 1,
+            axes,
+        });
+        self.simplify()
+    }
+
+    /// Twist this SDF around an axis through the origin, by `rate` radians per unit along it.
+    pub fn twist(self, axis: Vec3, rate: impl Into<Param>) -> Sdf {
+        let axis = axis.normalized();
+        self.wrap_in_deformation(|child| Distance::Twist {
+            child,
+            axis,
+            along: axis,
+            rate: rate.into(),
+        })
+    }
+
+    /// Bend this SDF around an axis through the origin, by `rate` radians per unit along
+    /// `along`, which must be perpendicular to `axis`. Straight lines along `along` become arcs.
+    pub fn bend(self, axis: Vec3, along: Vec3, rate: impl Into<Param>) -> Sdf {
+        let (axis, along) = (axis.normalized(), along.normalized());
+        self.wrap_in_deformation(|child| Distance::Twist {
+            child,
+            axis,
+            along,
+            rate: rate.into(),
+        })
+    }
+
+    /// Scale the cross-sections of this SDF perpendicular to an axis through the origin by
+    /// `1 + factor * h`, where `h` is the distance along the axis.
+    pub fn taper(self, axis: Vec3, factor: impl Into<Param>) -> Sdf {
+        let axis = axis.normalized();
+        self.wrap_in_deformation(|child| Distance::Taper {
+            child,
+            axis,
+            factor: factor.into(),
+        })
+    }
+
+    /// Rotate this SDF counter-clockwise around an axis through the origin, by `angle` radians.
+    /// Unlike `transform`, the angle can be animated, for things like hatches and propellers.
+    pub fn rotate(self, axis: Vec3, angle: impl Into<Param>) -> Sdf {
+        let axis = axis.normalized();
+        self.wrap_in_deformation(|child| Distance::Rotate {
+            child,
+            axis,
+            angle: angle.into(),
+        })
+    }
+
+    /// Stretch this SDF by cutting it along the coordinate planes and filling the gaps, so
+    /// that it becomes `2 * extent` longer along each axis.
+    pub fn elongate(self, extent: Vec3) -> Sdf {
+        assert!(
+            is_valid_extent(extent),
+            "extent must be finite and nonnegative"
+        );
+        self.wrap_in_deformation(|child| Distance::Elongate { child, extent })
+    }
+
+    /// Grow this SDF outwards by `amount`, or shrink it inwards if `amount` is negative.
+    pub fn offset(mut self, amount: impl Into<Param>) -> Sdf {
+        self.distances.push(Distance::Offset {
+            child: self.distances.len() - 1,
+            amount: amount.into(),
+        });
+        self.simplify()
+    }
+
+    /// Round off the edges of this SDF with radius `radius`, which also grows it by `radius`.
+    /// To round a shape without growing it, build it `radius` smaller first.
+    pub fn round(self, radius: impl Into<Param>) -> Sdf {
+        self.offset(radius)
+    }
+
+    /// Hollow this SDF out, leaving walls that extend `thickness` to either side of its surface.
+    pub fn shell(mut self, thickness: impl Into<Param>) -> Sdf {
+        self.distances.push(Distance::Shell {
+            child: self.distances.len() - 1,
+            thickness: thickness.into(),
+        });
+        self.simplify()
+    }
+
+    /// Displace the surface of this SDF by `octaves` of noise, sampled at `frequency` times the
+    /// point and scaled by `amplitude`. Adding octaves adds finer detail.
+    pub fn displace(
+        mut self,
+        noise: Noise,
+        amplitude: impl Into<Param>,
+        frequency: f32,
+        octaves: u32,
+    ) -> Sdf {
+        self.distances.push(Distance::Displace {
+            child: self.distances.len() - 1,
+            noise,
+            amplitude: amplitude.into(),
+            frequency,
+            octaves,
+        });
+        self.simplify()
+    }
+
+    fn wrap_in_deformation(mut self, deformation: impl FnOnce(usize) -> Distance) -> Sdf {
+        self.distances.push(deformation(self.distances.len() - 1));
+        self.simplify()
+    }
+
+    /// Get a bound on how much this SDF's distances overestimate the true distance, which
+    /// raymarchers divide their steps by so that they don't overshoot. Animations are at time zero.
+    #[must_use]
+    pub fn lipschitz(&self) -> f32 {
+        self.lipschitz_at_time(0.0)
+    }
+
+    /// Get the Lipschitz bound of this SDF at `t` seconds into its animations.
+    #[must_use]
+    pub fn lipschitz_at_time(&self, t: f32) -> f32 {
+        let mut bounds: Vec<f32> = vec![];
+        for distance in &self.distances {
+            let children = distance.children().into_iter().map(|child| bounds[child]);
+            bounds.push(distance.lipschitz(t) * children.fold(1.0, f32::max));
+        }
+        bounds.pop().expect("sdf has no distances")
+    }
+
+    /// Move every instance with the given id, without rebuilding the DAG.
+    /// Returns whether any instance was found.
+    pub fn set_instance_transform(&mut self, id: InstanceId, transform: Transform) -> bool {
+        let mut found = false;
+        for distance in &mut self.distances {
+            if let Distance::Transform {
+                inverse_transform,
+                instance: Some(instance),
+                ..
+            } = distance
+            {
+                if *instance == id {
+                    *inverse_transform = transform.inverse();
+                    found = true;
+                }
+            }
+        }
+        found
+    }
+}
+
+/// `a | b` is `a.union(b)`.
+impl BitOr for Sdf {
+    type Output = Sdf;
+    fn bitor(self, other: Sdf) -> Sdf {
+        self.union(other)
+    }
+}
+
+/// `a & b` is `a.intersect(b)`.
+impl BitAnd for Sdf {
+    type Output = Sdf;
+    fn bitand(self, other: Sdf) -> Sdf {
+        self.intersect(other)
+    }
+}
+
+/// `a - b` is `b.subtract(a)`: the points in `a` that are not in `b`.
+impl Sub for Sdf {
+    type Output = Sdf;
+    fn sub(self, other: Sdf) -> Sdf {
+        other.subtract(self)
+    }
+}
+
+/// `a ^ b` is `a.exclude(b)`.
+impl BitXor for Sdf {
+    type Output = Sdf;
+    fn bitxor(self, other: Sdf) -> Sdf {
+        self.exclude(other)
+    }
+}
+
+/// `a + v` is `a.translate(v)`.
+impl Add<Vec3> for Sdf {
+    type Output = Sdf;
+    fn add(self, vec: Vec3) -> Sdf {
+        self.translate(vec)
+    }
+}
+
+impl Sdf {
+    /// Replace this SDF with the result of an operation on it, for the `*Assign` operators.
+    fn update(&mut self, f: impl FnOnce(Sdf) -> Sdf) {
+        *self = f(std::mem::replace(self, Sdf::empty()));
+    }
+}
+
+impl BitOrAssign for Sdf {
+    fn bitor_assign(&mut self, other: Sdf) {
+        self.update(|sdf| sdf | other);
+    }
+}
+
+impl BitAndAssign for Sdf {
+    fn bitand_assign(&mut self, other: Sdf) {
+        self.update(|sdf| sdf & other);
+    }
+}
+
+impl SubAssign for Sdf {
+    fn sub_assign(&mut self, other: Sdf) {
+        self.update(|sdf| sdf - other);
+    }
+}
+
+impl BitXorAssign for Sdf {
+    fn bitxor_assign(&mut self, other: Sdf) {
+        self.update(|sdf| sdf ^ other);
+    }
+}
+
+impl AddAssign<Vec3> for Sdf {
+    fn add_assign(&mut self, vec: Vec3) {
+        self.update(|sdf| sdf + vec);
+    }
+}
+
+/// Collects SDFs into their union, like `Sdf::union_all`.
+impl FromIterator<Sdf> for Sdf {
+    fn from_iter<I: IntoIterator<Item = Sdf>>(iter: I) -> Sdf {
+        Sdf::union_all(iter)
+    }
+}
+
+/// A Gerstner wave, which is one of the components of `Sdf::ocean`.
+/// The water under the wave moves in circles, which sharpens its crests and flattens its troughs.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Wave {
+    /// The horizontal direction that the wave travels in. Its y component is ignored.
+    pub direction: Vec3,
+    /// The height of the crests above the undisturbed surface.
+    pub amplitude: f32,
+    /// The distance between crests.
+    pub wavelength: f32,
+    /// How fast the crests travel, in units per second.
+    pub speed: f32,
+}
+
+impl Wave {
+    fn wavenumber(&self) -> f32 {
+        2.0 * PI / self.wavelength
+    }
+
+    /// Get the phase of this wave at time `t`, at a point on the undisturbed surface.
+    fn phase<S: Scalar>(&self, p: Vector3<S>, t: S) -> S {
+        let scalar = S::from_f32;
+        scalar(self.wavenumber())
+            * (p.x * scalar(self.direction.x) + p.z * scalar(self.direction.z)
+                - scalar(self.speed) * t)
+    }
+}
+
+/// Check that an elongation's extent is finite and nonnegative along every axis.
+fn is_valid_extent(extent: Vec3) -> bool {
+    [extent.x, extent.y, extent.z]
+        .iter()
+        .all(|e| e.is_finite() && *e >= 0.0)
+}
+
+/// Normalize the directions of waves, ignoring their vertical components.
+fn horizontal_waves(waves: &[Wave]) -> Vec<Wave> {
+    waves
+        .iter()
+        .map(|wave| {
+            assert!(wave.wavelength > 0.0, "wavelength must be positive");
+            Wave {
+                direction: Vec3 {
+                    y: 0.0,
+                    ..wave.direction
+                }
+                .normalized(),
+                ..*wave
+            }
+        })
+        .collect()
+}
+
+/// The number of iterations used to find the water that an ocean's waves move under a point.
+const OCEAN_ITERATIONS: i32 = 4; // assert: equal to `OCEAN_ITERATIONS` in `shader.wgsl`
+
+/// The largest total steepness of an ocean's waves, which keeps its surface from folding over.
+const MAX_STEEPNESS: f32 = 0.5;
+
+/// Derived quantities that make an ocean's distance conservative.
+struct OceanBounds {
+    /// How far the water moves horizontally, relative to how far it moves vertically.
+    choppiness: f32,
+    /// A bound on the height error left after `OCEAN_ITERATIONS` iterations.
+    error: f32,
+    /// A bound on the slope of the surface, plus one.
+    lipschitz: f32,
+}
+
+impl OceanBounds {
+    fn new(waves: &[Wave]) -> OceanBounds {
+        // horizontal motion squeezes the surface, so it is limited to keep the slope finite
+        let slope: f32 = waves
+            .iter()
+            .map(|wave| wave.amplitude.abs() * wave.wavenumber())
+            .sum();
+        let choppiness = MAX_STEEPNESS / slope.max(MAX_STEEPNESS);
+        let contraction = choppiness * slope;
+        let reach = choppiness * waves.iter().map(|wave| wave.amplitude.abs()).sum::<f32>();
+        OceanBounds {
+            choppiness,
+            error: slope * reach * contraction.powi(OCEAN_ITERATIONS),
+            lipschitz: 1.0 + slope / (1.0 - contraction),
+        }
+    }
+}
+
+/// Get the height of an ocean's surface directly above or below `p`, relative to its origin.
+/// The water is moved both horizontally and vertically, so this finds the water that ends up
+/// above or below `p` by fixed-point iteration. This must match `ocean_height` in the shader.
+fn ocean_height<S: Scalar>(waves: &[Wave], choppiness: f32, p: Vector3<S>, t: S) -> S {
+    let scalar = S::from_f32;
+    let mut q = p;
+    for _ in 0..OCEAN_ITERATIONS {
+        let mut shift = Vector3::default();
+        for wave in waves {
+            let sin = wave.phase(q, t).sin();
+            shift += Vector3::from_f32(wave.direction)
+                * (scalar(choppiness) * scalar(wave.amplitude) * sin);
+        }
+        q = p + shift;
+    }
+    waves
+        .iter()
+        .map(|wave| scalar(wave.amplitude) * wave.phase(q, t).cos())
+        .fold(S::ZERO, S::add)
 }
 
 /// Represents the way that an object's pixels are colored.
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum Material {
     /// A basic material that sets all the pixels of an object to the same color.
     Flat(Color),
@@ -204,29 +1119,123 @@ pub const MAX_DIST: f32 = 10.0;
 pub const MAX_ITER: usize = 20;
 
 impl Sdf {
-    /// Get the signed distance to this `Sdf` from some point `p`.
+    /// Get the signed distance to this `Sdf` from some point `p`, with animations at time zero.
+    /// This is generic so that the simulation can use `Fixed` for results that are the same on
+    /// every machine, while rendering uses `f32`.
+    #[must_use]
+    pub fn distance<S: Scalar>(&self, p: Vector3<S>) -> S {
+        self.distance_at_time(p, S::ZERO)
+    }
+
+    /// Get the signed distance to this `Sdf` from some point `p`, at `t` seconds into its
+    /// animations. In `f32`, this matches what the shader renders when its `seconds` uniform is `t`.
     #[must_use]
-    pub fn distance(&self, p: Vec3) -> f32 {
-        let mut distances = vec![0.0; self.distances.len()];
-        for (i, node) in self.distances.iter().enumerate() {
-            distances[i] = match node {
-                Distance::Sphere { center, radius, .. } => (p - *center).length() - radius,
-                Distance::Plane { normal, offset, .. } => p.dot(*normal) - offset,
-                Distance::Union(x, y) => distances[*x].min(distances[*y]),
-                Distance::Intersection(x, y) => distances[*x].max(distances[*y]),
-                Distance::Exclusion(x, y) => distances[*x]
-                    .min(distances[*y])
-                    .max(-distances[*x].max(distances[*y])),
-                Distance::Subtraction(x, y) => (-distances[*x]).max(distances[*y]),
-            };
-        }
-        distances.pop().unwrap()
+    pub fn distance_at_time<S: Scalar>(&self, p: Vector3<S>, t: S) -> S {
+        self.distance_from(self.distances.len() - 1, p, t)
+    }
+
+    /// Get the gradient of the distance to this `Sdf` at some point `p`, with animations at time
+    /// zero, by central differences. Near the surface, it points outwards, along the normal.
+    pub fn gradient<S: Scalar>(&self, p: Vector3<S>) -> Vector3<S> {
+        let h = S::ONE / S::from_f32(1024.0);
+        Vector3::from([0, 1, 2].map(|axis| {
+            let mut offset = Vector3::from([S::ZERO; 3]);
+            offset[axis] = h;
+            (self.distance(p + offset) - self.distance(p - offset)) / (h + h)
+        }))
+    }
+
+    /// Get the signed distance to the subtree rooted at `node` from some point `p`, at time `t`.
+    fn distance_from<S: Scalar>(&self, node: usize, p: Vector3<S>, t: S) -> S {
+        let scalar = S::from_f32;
+        let vector = Vector3::from_f32;
+        match &self.distances[node] {
+            Distance::Sphere { center, radius, .. } => {
+                (p - vector(*center)).length() - radius.at(t)
+            }
+            Distance::Plane { normal, offset, .. } => p.dot(vector(*normal)) - offset.at(t),
+            Distance::Ocean { origin, waves, .. } => {
+                let bounds = OceanBounds::new(waves);
+                let q = p - vector(*origin);
+                q.y - ocean_height(waves, bounds.choppiness, q, t) - scalar(bounds.error)
+            }
+            Distance::Empty => S::INFINITY,
+            Distance::Everything => S::NEG_INFINITY,
+            Distance::Union(x, y) => self
+                .distance_from(*x, p, t)
+                .min(self.distance_from(*y, p, t)),
+            Distance::Intersection(x, y) => self
+                .distance_from(*x, p, t)
+                .max(self.distance_from(*y, p, t)),
+            Distance::Exclusion(x, y) => {
+                let (x, y) = (self.distance_from(*x, p, t), self.distance_from(*y, p, t));
+                x.min(y).max(-x.max(y))
+            }
+            Distance::Subtraction(x, y) => {
+                (-self.distance_from(*x, p, t)).max(self.distance_from(*y, p, t))
+            }
+            Distance::Transform {
+                child,
+                inverse_transform,
+                ..
+            } => {
+                let q = inverse_transform.transform_point(p);
+                self.distance_from(*child, q, t) / scalar(inverse_transform.scale())
+            }
+            Distance::Repeat {
+                child,
+                period,
+                counts,
+            } => repeat_points(p, *period, *counts)
+                .into_iter()
+                .map(|q| self.distance_from(*child, q, t))
+                .fold(S::INFINITY, S::min),
+            Distance::Mirror {
+                child,
+                normal,
+                offset,
+            } => {
+                let normal = vector(*normal);
+                let behind = (p.dot(normal) - offset.at(t)).min(S::ZERO);
+                self.distance_from(*child, p - normal * (behind + behind), t)
+            }
+            Distance::Symmetric { child, axes } => {
+                let mut q = p;
+                for axis in 0..3 {
+                    if axes[axis] {
+                        q[axis] = q[axis].abs();
+                    }
+                }
+                self.distance_from(*child, q, t)
+            }
+            Distance::Twist { child, .. }
+            | Distance::Taper { child, .. }
+            | Distance::Rotate { child, .. }
+            | Distance::Elongate { child, .. } => {
+                self.distance_from(*child, self.distances[node].deform(p, t), t)
+            }
+            Distance::Offset { child, amount } => self.distance_from(*child, p, t) - amount.at(t),
+            Distance::Shell { child, thickness } => {
+                self.distance_from(*child, p, t).abs() - thickness.at(t)
+            }
+            Distance::Displace {
+                child,
+                noise,
+                amplitude,
+                frequency,
+                octaves,
+            } => {
+                let noise = noise.fbm(p * scalar(*frequency), *octaves);
+                self.distance_from(*child, p, t) + amplitude.at(t) * noise
+            }
+        }
     }
 
     /// Find where the given ray collides with this SDF, if it does.
     pub fn raymarch(&self, pos: Vec3, mut dir: Vec3) -> Raymarch {
         let mut steps = 0;
         let mut accum = 0.0;
+        let lipschitz = self.lipschitz();
         dir = dir.normalized();
         while steps < MAX_ITER {
             let point = pos + dir * accum;
@@ -238,12 +1247,56 @@ impl Sdf {
                 return Raymarch::WentTooFar;
             }
             steps += 1;
-            accum += distance;
+            accum += distance / lipschitz;
         }
         Raymarch::TookTooLong
     }
 }
 
+/// Get the points that a repeated child should be evaluated at: the point in the cell containing
+/// `p`, and the points in the nearest neighboring cells along each repeated axis, so that a child
+/// that spills into its neighbors is still found. This must match `repeat_point` in the shader.
+#[allow(clippy::float_cmp)] // cell ids are whole numbers
+fn repeat_points<S: Scalar>(
+    p: Vector3<S>,
+    period: Vec3,
+    counts: Option<[u32; 3]>,
+) -> Vec<Vector3<S>> {
+    let mut points = vec![p];
+    for axis in 0..3 {
+        if period[axis] == 0.0 {
+            continue;
+        }
+        let s = S::from_f32(period[axis]);
+        let clamp = |id: S| match counts {
+            Some(counts) => id.clamp(S::ZERO, S::from_f32((counts[axis] - 1) as f32)),
+            None => id,
+        };
+        let id = (p[axis] / s).round();
+        let local = p[axis] - s * id;
+        let own = clamp(id);
+        let neighbor = clamp(id + if local < S::ZERO { -S::ONE } else { S::ONE });
+        for i in 0..points.len() {
+            let mut q = points[i];
+            q[axis] = p[axis] - s * own;
+            points[i] = q;
+            if neighbor != own {
+                q[axis] = p[axis] - s * neighbor;
+                points.push(q);
+            }
+        }
+    }
+    points
+}
+
+/// Pack the axes of a `Symmetric` node into bits, with x in the lowest bit.
+fn axes_mask(axes: [bool; 3]) -> u8 {
+    axes.into_iter()
+        .enumerate()
+        .map(|(axis, on)| u8::from(on) << axis)
+        .sum()
+}
+
 /// A representation of the `Distance` enum that matches the struct in the shader.
 #[repr(C)]
 pub struct GpuDistance {
@@ -263,13 +1316,44 @@ pub struct GpuMaterial {
     b: f32,
 }
 
+/// A parameter of a `Distance` that doesn't fit in a `GpuDistance`, for the shader.
+pub type GpuParameter = [f32; 4];
+
+/// The contents of the buffers that the shader reads an `Sdf` from.
+pub type GpuRepr = (Vec<GpuDistance>, Vec<GpuMaterial>, Vec<GpuParameter>);
+
 const MAGIC_U32: u32 = 0xDEAD_BEEF;
 const MAGIC_F32: f32 = -12.34;
 
+/// The size of the shader's traversal stack, which limits how deep an `Sdf` can be.
+pub const MAX_DEPTH: usize = 64;
+
 impl Sdf {
-    /// Convert this `SDF` to a representation that can be sent to the shader.
+    /// Get the length of the longest path from the root to a primitive, counting both ends.
     #[must_use]
-    pub fn to_gpu_repr(&self) -> (Vec<GpuDistance>, Vec<GpuMaterial>) {
+    pub fn depth(&self) -> usize {
+        let mut depths: Vec<usize> = vec![];
+        for distance in &self.distances {
+            let children = distance.children().into_iter().map(|child| depths[child]);
+            depths.push(1 + children.max().unwrap_or(0));
+        }
+        depths.pop().expect("sdf has no distances")
+    }
+
+    /// Convert this `SDF` to a representation that can be sent to the shader.
+    /// None of the buffers are empty, because empty buffers can't be bound.
+    pub fn to_gpu_repr(&self) -> Result<GpuRepr, Error> {
+        self.to_gpu_repr_at_time(0.0)
+    }
+
+    /// Convert this `SDF` to a representation that can be sent to the shader, with its animated
+    /// parameters evaluated at time `t`. Oceans are animated by the shader's `seconds` instead.
+    pub fn to_gpu_repr_at_time(&self, t: f32) -> Result<GpuRepr, Error> {
+        let depth = self.depth();
+        if depth > MAX_DEPTH {
+            return Err(Error::TooDeep { depth });
+        }
+        let mut parameters = vec![];
         let distances: Vec<_> = self
             .distances
             .iter()
@@ -283,7 +1367,7 @@ impl Sdf {
                     x: *material as u32,
                     y: MAGIC_U32,
                     _padding: MAGIC_U32,
-                    v: [center.x, center.y, center.z, *radius],
+                    v: [center.x, center.y, center.z, radius.at(t)],
                 },
                 Distance::Plane {
                     normal,
@@ -294,7 +1378,54 @@ impl Sdf {
                     x: *material as u32,
                     y: MAGIC_U32,
                     _padding: MAGIC_U32,
-                    v: [normal.x, normal.y, normal.z, *offset],
+                    v: [normal.x, normal.y, normal.z, offset.at(t)],
+                },
+                Distance::Ocean {
+                    origin,
+                    waves,
+                    material,
+                } => {
+                    // a header, followed by two parameters per wave
+                    let offset = parameters.len() as u32;
+                    let bounds = OceanBounds::new(waves);
+                    parameters.push([
+                        waves.len() as f32,
+                        bounds.choppiness,
+                        bounds.error,
+                        MAGIC_F32,
+                    ]);
+                    for wave in waves {
+                        let Wave {
+                            direction,
+                            amplitude,
+                            wavelength,
+                            speed,
+                        } = *wave;
+                        parameters.push([direction.x, direction.z, amplitude, wavelength]);
+                        parameters.push([speed, MAGIC_F32, MAGIC_F32, MAGIC_F32]);
+                    }
+                    GpuDistance {
+                        tag: 16,
+                        x: *material as u32,
+                        y: offset,
+                        _padding: MAGIC_U32,
+                        v: [origin.x, origin.y, origin.z, MAGIC_F32],
+                    }
+                }
+                // the shader can't represent infinities, so these are as far as a float can go
+                Distance::Empty => GpuDistance {
+                    tag: 18,
+                    x: MAGIC_U32,
+                    y: MAGIC_U32,
+                    _padding: MAGIC_U32,
+                    v: [f32::MAX, MAGIC_F32, MAGIC_F32, MAGIC_F32],
+                },
+                Distance::Everything => GpuDistance {
+                    tag: 19,
+                    x: MAGIC_U32,
+                    y: MAGIC_U32,
+                    _padding: MAGIC_U32,
+                    v: [-f32::MAX, MAGIC_F32, MAGIC_F32, MAGIC_F32],
                 },
                 Distance::Union(x, y) => GpuDistance {
                     tag: 2,
@@ -324,9 +1455,130 @@ impl Sdf {
                     _padding: MAGIC_U32,
                     v: [MAGIC_F32, MAGIC_F32, MAGIC_F32, MAGIC_F32],
                 },
+                Distance::Transform {
+                    child,
+                    inverse_transform: t,
+                    ..
+                } => {
+                    // the rows of the matrix, so that the shader can use dot products
+                    let offset = parameters.len() as u32;
+                    parameters.push([t.x.x, t.y.x, t.z.x, t.w.x]);
+                    parameters.push([t.x.y, t.y.y, t.z.y, t.w.y]);
+                    parameters.push([t.x.z, t.y.z, t.z.z, t.w.z]);
+                    GpuDistance {
+                        tag: 6,
+                        x: *child as u32,
+                        y: offset,
+                        _padding: MAGIC_U32,
+                        v: [t.scale().recip(), MAGIC_F32, MAGIC_F32, MAGIC_F32],
+                    }
+                }
+                Distance::Repeat {
+                    child,
+                    period,
+                    counts,
+                } => {
+                    // an infinite count is stored as zero
+                    let offset = parameters.len() as u32;
+                    let [x, y, z] = counts.unwrap_or([0; 3]).map(|count| count as f32);
+                    parameters.push([x, y, z, MAGIC_F32]);
+                    GpuDistance {
+                        tag: 7,
+                        x: *child as u32,
+                        y: offset,
+                        _padding: MAGIC_U32,
+                        v: [period.x, period.y, period.z, MAGIC_F32],
+                    }
+                }
+                Distance::Mirror {
+                    child,
+                    normal,
+                    offset,
+                } => GpuDistance {
+                    tag: 8,
+                    x: *child as u32,
+                    y: MAGIC_U32,
+                    _padding: MAGIC_U32,
+                    v: [normal.x, normal.y, normal.z, offset.at(t)],
+                },
+                Distance::Symmetric { child, axes } => GpuDistance {
+                    tag: 9,
+                    x: *child as u32,
+                    y: axes_mask(*axes).into(),
+                    _padding: MAGIC_U32,
+                    v: [MAGIC_F32, MAGIC_F32, MAGIC_F32, MAGIC_F32],
+                },
+                Distance::Twist {
+                    child,
+                    axis,
+                    along,
+                    rate,
+                } => {
+                    let offset = parameters.len() as u32;
+                    parameters.push([along.x, along.y, along.z, MAGIC_F32]);
+                    GpuDistance {
+                        tag: 10,
+                        x: *child as u32,
+                        y: offset,
+                        _padding: MAGIC_U32,
+                        v: [axis.x, axis.y, axis.z, rate.at(t)],
+                    }
+                }
+                Distance::Taper {
+                    child,
+                    axis,
+                    factor,
+                } => GpuDistance {
+                    tag: 11,
+                    x: *child as u32,
+                    y: MAGIC_U32,
+                    _padding: MAGIC_U32,
+                    v: [axis.x, axis.y, axis.z, factor.at(t)],
+                },
+                Distance::Rotate { child, axis, angle } => GpuDistance {
+                    tag: 17,
+                    x: *child as u32,
+                    y: MAGIC_U32,
+                    _padding: MAGIC_U32,
+                    v: [axis.x, axis.y, axis.z, angle.at(t)],
+                },
+                Distance::Elongate { child, extent } => GpuDistance {
+                    tag: 12,
+                    x: *child as u32,
+                    y: MAGIC_U32,
+                    _padding: MAGIC_U32,
+                    v: [extent.x, extent.y, extent.z, MAGIC_F32],
+                },
+                Distance::Offset { child, amount } => GpuDistance {
+                    tag: 13,
+                    x: *child as u32,
+                    y: MAGIC_U32,
+                    _padding: MAGIC_U32,
+                    v: [amount.at(t), MAGIC_F32, MAGIC_F32, MAGIC_F32],
+                },
+                Distance::Shell { child, thickness } => GpuDistance {
+                    tag: 14,
+                    x: *child as u32,
+                    y: MAGIC_U32,
+                    _padding: MAGIC_U32,
+                    v: [thickness.at(t), MAGIC_F32, MAGIC_F32, MAGIC_F32],
+                },
+                Distance::Displace {
+                    child,
+                    noise: Noise::Gradient(seed),
+                    amplitude,
+                    frequency,
+                    octaves,
+                } => GpuDistance {
+                    tag: 15,
+                    x: *child as u32,
+                    y: *seed,
+                    _padding: MAGIC_U32,
+                    v: [amplitude.at(t), *frequency, *octaves as f32, MAGIC_F32],
+                },
             })
             .collect();
-        let materials: Vec<_> = self
+        let mut materials: Vec<_> = self
             .materials
             .iter()
             .map(|material| match material {
@@ -338,6 +1590,36 @@ impl Sdf {
                 },
             })
             .collect();
-        (distances, materials)
+        if materials.is_empty() {
+            materials.push(GpuMaterial {
+                tag: MAGIC_U32,
+                r: MAGIC_F32,
+                g: MAGIC_F32,
+                b: MAGIC_F32,
+            });
+        }
+        if parameters.is_empty() {
+            parameters.push([MAGIC_F32, MAGIC_F32, MAGIC_F32, MAGIC_F32]);
+        }
+        Ok((distances, materials, parameters))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ball() -> Sdf {
+        Sdf::sphere(1.0, Material::Flat(Color::WHITE))
+    }
+
+    #[test]
+    #[should_panic(expected = "extent must be finite and nonnegative")]
+    fn elongate_rejects_a_negative_extent() {
+        let _ = ball().elongate(Vec3 {
+            x: 1.0,
+            y: -0.5,
+            z: 0.0,
+        });
     }
 }
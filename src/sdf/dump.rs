@@ -0,0 +1,245 @@
+//! Human-readable dumps of `Sdf` DAGs, for debugging.
+
+use super::*;
+use std::fmt::Write;
+
+impl Distance {
+    /// Get the name of this kind of node.
+    fn kind(&self) -> &'static str {
+        match self {
+            Distance::Sphere { .. } => "sphere",
+            Distance::Plane { .. } => "plane",
+            Distance::Ocean { .. } => "ocean",
+            Distance::Empty => "empty",
+            Distance::Everything => "everything",
+            Distance::Union(..) => "union",
+            Distance::Intersection(..) => "intersection",
+            Distance::Exclusion(..) => "exclusion",
+            Distance::Subtraction(..) => "subtraction",
+            Distance::Transform { .. } => "transform",
+            Distance::Repeat { .. } => "repeat",
+            Distance::Mirror { .. } => "mirror",
+            Distance::Symmetric { .. } => "symmetric",
+            Distance::Twist { axis, along, .. } if axis == along => "twist",
+            Distance::Twist { .. } => "bend",
+            Distance::Taper { .. } => "taper",
+            Distance::Rotate { .. } => "rotate",
+            Distance::Elongate { .. } => "elongate",
+            Distance::Offset { .. } => "offset",
+            Distance::Shell { .. } => "shell",
+            Distance::Displace { .. } => "displace",
+        }
+    }
+
+    /// Get the parameters of this node, excluding its children.
+    fn parameters(&self) -> String {
+        match self {
+            Distance::Sphere { center, radius, .. } => {
+                format!("center={center:?} radius={radius:?}")
+            }
+            Distance::Plane { normal, offset, .. } | Distance::Mirror { normal, offset, .. } => {
+                format!("normal={normal:?} offset={offset:?}")
+            }
+            Distance::Ocean { origin, waves, .. } => format!("origin={origin:?} waves={waves:?}"),
+            Distance::Empty
+            | Distance::Everything
+            | Distance::Union(..)
+            | Distance::Intersection(..)
+            | Distance::Exclusion(..)
+            | Distance::Subtraction(..) => String::new(),
+            Distance::Transform {
+                inverse_transform,
+                instance,
+                ..
+            } => match instance {
+                Some(InstanceId(id)) => format!("instance={id} inverse={inverse_transform:?}"),
+                None => format!("inverse={inverse_transform:?}"),
+            },
+            Distance::Repeat { period, counts, .. } => match counts {
+                Some(counts) => format!("period={period:?} counts={counts:?}"),
+                None => format!("period={period:?}"),
+            },
+            Distance::Symmetric { axes, .. } => format!("axes={axes:?}"),
+            Distance::Twist {
+                axis, along, rate, ..
+            } => format!("axis={axis:?} along={along:?} rate={rate:?}"),
+            Distance::Taper { axis, factor, .. } => format!("axis={axis:?} factor={factor:?}"),
+            Distance::Rotate { axis, angle, .. } => format!("axis={axis:?} angle={angle:?}"),
+            Distance::Elongate { extent, .. } => format!("extent={extent:?}"),
+            Distance::Offset { amount, .. } => format!("amount={amount:?}"),
+            Distance::Shell { thickness, .. } => format!("thickness={thickness:?}"),
+            Distance::Displace {
+                noise,
+                amplitude,
+                frequency,
+                octaves,
+                ..
+            } => format!(
+                "noise={noise:?} amplitude={amplitude:?} frequency={frequency:?} octaves={octaves}"
+            ),
+        }
+    }
+}
+
+impl Material {
+    /// Get this material's color as a `#rrggbb` string.
+    fn hex(&self) -> String {
+        let byte = |x: f64| (x.clamp(0.0, 1.0) * 255.0).round() as u8;
+        match self {
+            Material::Flat(color) => {
+                format!(
+                    "#{:02x}{:02x}{:02x}",
+                    byte(color.r),
+                    byte(color.g),
+                    byte(color.b)
+                )
+            }
+        }
+    }
+}
+
+impl Sdf {
+    /// Count how many times each node is pointed at by another node.
+    fn parent_counts(&self) -> Vec<usize> {
+        let mut counts = vec![0; self.distances.len()];
+        for distance in &self.distances {
+            for child in distance.children() {
+                counts[child] += 1;
+            }
+        }
+        counts
+    }
+
+    /// Render this `Sdf` as a Graphviz DOT graph.
+    /// Nodes that are shared by several parents are drawn with a double border.
+    #[must_use]
+    pub fn to_dot(&self) -> String {
+        let counts = self.parent_counts();
+        let mut out =
+            String::from("digraph sdf {\n\tnode [shape=box, style=filled, fillcolor=white];\n");
+        for (i, distance) in self.distances.iter().enumerate() {
+            let mut label = format!("#{i} {}", distance.kind());
+            for name in self.names_of(i) {
+                write!(label, " \\\"{}\\\"", name.escape_default()).unwrap();
+            }
+            let parameters = distance.parameters();
+            if !parameters.is_empty() {
+                write!(label, "\\n{parameters}").unwrap();
+            }
+            let mut attributes = String::new();
+            if let Some(material) = distance.material() {
+                let hex = self.materials[material].hex();
+                write!(label, "\\nmaterial {material} {hex}").unwrap();
+                write!(attributes, ", fillcolor=\"{hex}\"").unwrap();
+            }
+            if counts[i] > 1 {
+                attributes.push_str(", peripheries=2");
+            }
+            writeln!(out, "\tn{i} [label=\"{label}\"{attributes}];").unwrap();
+            for (j, child) in distance.children().into_iter().enumerate() {
+                writeln!(out, "\tn{i} -> n{child} [label=\"{j}\"];").unwrap();
+            }
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    fn fmt_node(
+        &self,
+        f: &mut std::fmt::Formatter,
+        node: usize,
+        depth: usize,
+        counts: &[usize],
+        visited: &mut [bool],
+    ) -> std::fmt::Result {
+        let distance = &self.distances[node];
+        write!(
+            f,
+            "{:indent$}#{node} {}",
+            "",
+            distance.kind(),
+            indent = depth * 2
+        )?;
+        for name in self.names_of(node) {
+            write!(f, " {name:?}")?;
+        }
+        if counts[node] > 1 {
+            if visited[node] {
+                return writeln!(f, " (shared, see above)");
+            }
+            write!(f, " (shared by {})", counts[node])?;
+        }
+        visited[node] = true;
+
+        let parameters = distance.parameters();
+        if !parameters.is_empty() {
+            write!(f, " {parameters}")?;
+        }
+        if let Some(material) = distance.material() {
+            write!(f, " material={material} {:?}", self.materials[material])?;
+        }
+        writeln!(f)?;
+
+        for child in distance.children() {
+            self.fmt_node(f, child, depth + 1, counts, visited)?;
+        }
+        Ok(())
+    }
+}
+
+/// Formats a node on one line, with its children and material by index, for desync dumps.
+struct Line<'a>(&'a Distance);
+
+impl std::fmt::Display for Line<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.0.kind())?;
+        let parameters = self.0.parameters();
+        if !parameters.is_empty() {
+            write!(f, " {parameters}")?;
+        }
+        for child in self.0.children() {
+            write!(f, " #{child}")?;
+        }
+        if let Some(material) = self.0.material() {
+            write!(f, " material={material}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Hashes the encoding, which has the exact bits of every material and node, and dumps a line for
+/// each of them, so that a diff of two dumps points at the first one that differs.
+impl StableHash for Sdf {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        for (i, material) in self.materials.iter().enumerate() {
+            hasher.field_with(format_args!("materials[{i}]"), |hasher| {
+                hasher.write(&[], format_args!("{material:?}"));
+            });
+        }
+        for (i, distance) in self.distances.iter().enumerate() {
+            hasher.field_with(format_args!("distances[{i}]"), |hasher| {
+                hasher.write(&[], Line(distance));
+            });
+        }
+        let bytes = self.to_bytes();
+        hasher.field_with("encoding", |hasher| {
+            hasher.write(&bytes, format_args!("{:016x}", fnv1a(&bytes)));
+        });
+    }
+}
+
+/// Prints the DAG as an indented tree, starting from the root.
+/// Shared subtrees are printed in full the first time they appear and referenced afterwards.
+impl std::fmt::Display for Sdf {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let counts = self.parent_counts();
+        let mut visited = vec![false; self.distances.len()];
+        self.fmt_node(f, self.distances.len() - 1, 0, &counts, &mut visited)
+    }
+}
+
+impl std::fmt::Debug for Sdf {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        std::fmt::Display::fmt(self, f)
+    }
+}
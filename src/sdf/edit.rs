@@ -0,0 +1,111 @@
+//! Named nodes, and setters that change them in place without rebuilding the DAG.
+//!
+//! Names are kept alongside the nodes and remapped whenever nodes move, so they survive `append`
+//! and `simplify`. Named nodes are never merged with equal nodes, so that changing one doesn't
+//! change any others.
+
+use super::*;
+
+/// Identifies a node of an `Sdf`, as returned by `Sdf::find`.
+/// It is only valid for the `Sdf` that returned it, until that `Sdf` is consumed by a constructor.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct NodeId(usize);
+
+impl Sdf {
+    /// Name the root of this SDF, so that it can still be found with `find` after this SDF has
+    /// been combined with others.
+    pub fn named(mut self, name: impl Into<String>) -> Sdf {
+        let root = self.distances.len() - 1;
+        self.names.push((name.into(), root));
+        self
+    }
+
+    /// Find the node with the given name. If several nodes have it, the first one named is found.
+    #[must_use]
+    pub fn find(&self, name: &str) -> Option<NodeId> {
+        self.names
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, node)| NodeId(*node))
+    }
+
+    /// Get the names of a node, in the order that they were given.
+    pub(super) fn names_of(&self, node: usize) -> impl Iterator<Item = &str> {
+        self.names
+            .iter()
+            .filter(move |(_, n)| *n == node)
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// Change the center and radius of a sphere. Returns whether the node is a sphere.
+    pub fn set_sphere(&mut self, node: NodeId, center: Vec3, radius: impl Into<Param>) -> bool {
+        match &mut self.distances[node.0] {
+            Distance::Sphere {
+                center: c,
+                radius: r,
+                ..
+            } => {
+                (*c, *r) = (center, radius.into());
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Change the normal and offset of a plane. Returns whether the node is a plane.
+    pub fn set_plane(&mut self, node: NodeId, normal: Vec3, offset: impl Into<Param>) -> bool {
+        match &mut self.distances[node.0] {
+            Distance::Plane {
+                normal: n,
+                offset: o,
+                ..
+            } => {
+                (*n, *o) = (normal, offset.into());
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Change the waves of an ocean. Returns whether the node is an ocean.
+    pub fn set_waves(&mut self, node: NodeId, waves: &[Wave]) -> bool {
+        match &mut self.distances[node.0] {
+            Distance::Ocean { waves: w, .. } => {
+                *w = horizontal_waves(waves);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Change the transformation of a node made by `transform` or `instance`.
+    /// Returns whether the node is a transformation.
+    pub fn set_transform(&mut self, node: NodeId, transform: Transform) -> bool {
+        match &mut self.distances[node.0] {
+            Distance::Transform {
+                inverse_transform, ..
+            } => {
+                *inverse_transform = transform.inverse();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Change the material of a primitive. Returns whether the node is a primitive.
+    pub fn set_material(&mut self, node: NodeId, material: Material) -> bool {
+        let Some(old) = self.distances[node.0].material() else {
+            return false;
+        };
+        // materials are shared between equal primitives, so a shared one is copied first
+        let users = self.distances.iter().filter(|d| d.material() == Some(old));
+        if users.count() > 1 {
+            self.materials.push(material);
+            let new = self.materials.len() - 1;
+            self.distances[node.0].map_material(|_| new);
+        } else {
+            self.materials[old] = material;
+        }
+        true
+    }
+}
@@ -0,0 +1,989 @@
+//! A versioned, little-endian binary encoding of `Sdf`s, for networking and caching.
+//!
+//! Layout, with all integers and floats little-endian:
+//! - the magic bytes `LSDF` and a `u32` version,
+//! - a `u32` material count, followed by that many tagged materials,
+//! - a `u32` distance count, followed by that many tagged distances.
+//!
+//! Each entry starts with a `u8` tag. Distance tags match the ones used by the shader.
+//! Scalar parameters are an `f32` constant followed by a tagged channel, where tag 0 means none.
+
+use super::*;
+
+const MAGIC: [u8; 4] = *b"LSDF";
+const VERSION: u32 = 2;
+
+/// The maximum amount that the length of a plane normal can differ from 1.
+const NORMAL_TOLERANCE: f32 = 1e-4;
+
+/// The reasons that `Sdf::from_bytes` can reject its input.
+#[derive(Debug, PartialEq)]
+pub enum DecodeError {
+    /// The input ended in the middle of a value.
+    UnexpectedEnd,
+    /// The input did not start with the expected magic bytes.
+    BadMagic,
+    /// The input was written by an unsupported version of the encoding.
+    UnsupportedVersion(u32),
+    /// The input had bytes left over after the last distance.
+    TrailingBytes,
+    /// The input did not contain any distances, so it has no root.
+    Empty,
+    /// A material had a tag that this version does not know about.
+    UnknownMaterialTag(u8),
+    /// A distance had a tag that this version does not know about.
+    UnknownDistanceTag(u8),
+    /// A noise had a tag that this version does not know about.
+    UnknownNoiseTag(u8),
+    /// A channel had a tag that this version does not know about.
+    UnknownChannelTag(u8),
+    /// A keyframe had an interpolation tag that this version does not know about.
+    UnknownInterpolationTag(u8),
+    /// A channel's keyframes were not sorted by time.
+    UnsortedKeyframes,
+    /// An optional field of a distance had a tag other than 0 for none or 1 for some.
+    UnknownOptionTag {
+        /// The index of the offending distance.
+        node: usize,
+        /// The tag.
+        tag: u8,
+    },
+    /// A distance pointed at a distance without a strictly smaller index.
+    ForwardReference {
+        /// The index of the offending distance.
+        node: usize,
+        /// The index that it pointed at.
+        child: usize,
+    },
+    /// A distance pointed at a material that does not exist.
+    MissingMaterial {
+        /// The index of the offending distance.
+        node: usize,
+        /// The index that it pointed at.
+        material: usize,
+    },
+    /// A plane had a normal without unit length.
+    UnnormalizedNormal {
+        /// The index of the offending distance.
+        node: usize,
+    },
+    /// A repetition had a negative period or a zero count.
+    InvalidRepetition {
+        /// The index of the offending distance.
+        node: usize,
+    },
+    /// A symmetry had bits set other than the ones for the three axes.
+    InvalidAxes {
+        /// The index of the offending distance.
+        node: usize,
+    },
+    /// An ocean had a wave that was not horizontal or had a nonpositive wavelength.
+    InvalidWave {
+        /// The index of the offending distance.
+        node: usize,
+    },
+    /// An elongation had a negative extent.
+    InvalidExtent {
+        /// The index of the offending distance.
+        node: usize,
+    },
+    /// A transformation was not a similarity: a rotation, a uniform scale and a translation.
+    NotSimilarity {
+        /// The index of the offending distance.
+        node: usize,
+    },
+    /// A value was NaN.
+    NaN,
+    /// A value was infinite.
+    Infinite,
+}
+
+impl std::fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            DecodeError::UnexpectedEnd => write!(f, "unexpected end of input"),
+            DecodeError::BadMagic => write!(f, "input is not an encoded sdf"),
+            DecodeError::UnsupportedVersion(v) => write!(f, "unsupported version {v}"),
+            DecodeError::TrailingBytes => write!(f, "trailing bytes after the last distance"),
+            DecodeError::Empty => write!(f, "sdf has no distances"),
+            DecodeError::UnknownMaterialTag(t) => write!(f, "unknown material tag {t}"),
+            DecodeError::UnknownDistanceTag(t) => write!(f, "unknown distance tag {t}"),
+            DecodeError::UnknownNoiseTag(t) => write!(f, "unknown noise tag {t}"),
+            DecodeError::UnknownChannelTag(t) => write!(f, "unknown channel tag {t}"),
+            DecodeError::UnknownInterpolationTag(t) => write!(f, "unknown interpolation tag {t}"),
+            DecodeError::UnsortedKeyframes => write!(f, "keyframes are not sorted by time"),
+            DecodeError::UnknownOptionTag { node, tag } => {
+                write!(f, "distance {node} has an unknown option tag {tag}")
+            }
+            DecodeError::ForwardReference { node, child } => {
+                write!(f, "distance {node} points forward to distance {child}")
+            }
+            DecodeError::MissingMaterial { node, material } => {
+                write!(f, "distance {node} points to missing material {material}")
+            }
+            DecodeError::UnnormalizedNormal { node } => {
+                write!(f, "distance {node} has a normal without unit length")
+            }
+            DecodeError::InvalidRepetition { node } => {
+                write!(f, "distance {node} has a negative period or a zero count")
+            }
+            DecodeError::InvalidAxes { node } => {
+                write!(f, "distance {node} has an axis mask with unknown bits")
+            }
+            DecodeError::InvalidWave { node } => {
+                write!(
+                    f,
+                    "distance {node} has a wave that is not horizontal or has a nonpositive wavelength"
+                )
+            }
+            DecodeError::InvalidExtent { node } => {
+                write!(f, "distance {node} has a negative extent")
+            }
+            DecodeError::NotSimilarity { node } => {
+                write!(
+                    f,
+                    "distance {node} has a transformation that is not a rotation, uniform scale and translation"
+                )
+            }
+            DecodeError::NaN => write!(f, "value is NaN"),
+            DecodeError::Infinite => write!(f, "value is infinite"),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+struct Writer(Vec<u8>);
+
+impl Writer {
+    fn u8(&mut self, x: u8) {
+        self.0.push(x);
+    }
+
+    fn u32(&mut self, x: u32) {
+        self.0.extend_from_slice(&x.to_le_bytes());
+    }
+
+    fn index(&mut self, x: usize) {
+        self.u32(u32::try_from(x).expect("index does not fit in a u32"));
+    }
+
+    fn f32(&mut self, x: f32) {
+        self.0.extend_from_slice(&x.to_le_bytes());
+    }
+
+    fn f64(&mut self, x: f64) {
+        self.0.extend_from_slice(&x.to_le_bytes());
+    }
+
+    fn vec3(&mut self, v: Vec3) {
+        self.f32(v.x);
+        self.f32(v.y);
+        self.f32(v.z);
+    }
+
+    fn transform(&mut self, t: Transform) {
+        self.vec3(t.x);
+        self.vec3(t.y);
+        self.vec3(t.z);
+        self.vec3(t.w);
+    }
+
+    fn param(&mut self, param: &Param) {
+        self.f32(param.base);
+        match &param.channel {
+            None => self.u8(0),
+            Some(Channel::Keyframes(keyframes)) => {
+                self.u8(1);
+                self.index(keyframes.len());
+                for key in keyframes {
+                    self.f32(key.time);
+                    self.f32(key.value);
+                    self.f32(key.slope);
+                    self.u8(match key.interpolation {
+                        Interpolation::Step => 0,
+                        Interpolation::Linear => 1,
+                        Interpolation::Hermite => 2,
+                    });
+                }
+            }
+            Some(Channel::Linear(rate)) => {
+                self.u8(2);
+                self.f32(*rate);
+            }
+            Some(Channel::Sine {
+                amplitude,
+                frequency,
+                phase,
+            }) => {
+                self.u8(3);
+                self.f32(*amplitude);
+                self.f32(*frequency);
+                self.f32(*phase);
+            }
+        }
+    }
+}
+
+/// The input ended in the middle of a value. Each format's error type converts from this.
+pub(crate) struct UnexpectedEnd;
+
+impl From<UnexpectedEnd> for DecodeError {
+    fn from(_: UnexpectedEnd) -> DecodeError {
+        DecodeError::UnexpectedEnd
+    }
+}
+
+/// Reads little-endian values from the front of a byte slice, for this format and replays.
+pub(crate) struct Reader<'a>(pub(crate) &'a [u8]);
+
+impl Reader<'_> {
+    pub(crate) fn take<const N: usize>(&mut self) -> Result<[u8; N], UnexpectedEnd> {
+        if self.0.len() < N {
+            return Err(UnexpectedEnd);
+        }
+        let (head, tail) = self.0.split_at(N);
+        self.0 = tail;
+        Ok(head.try_into().unwrap())
+    }
+
+    pub(crate) fn u8(&mut self) -> Result<u8, UnexpectedEnd> {
+        Ok(self.take::<1>()?[0])
+    }
+
+    pub(crate) fn u32(&mut self) -> Result<u32, UnexpectedEnd> {
+        Ok(u32::from_le_bytes(self.take()?))
+    }
+
+    pub(crate) fn u64(&mut self) -> Result<u64, UnexpectedEnd> {
+        Ok(u64::from_le_bytes(self.take()?))
+    }
+
+    fn index(&mut self) -> Result<usize, DecodeError> {
+        Ok(self.u32()? as usize)
+    }
+
+    fn f32(&mut self) -> Result<f32, DecodeError> {
+        let x = f32::from_le_bytes(self.take()?);
+        if x.is_nan() {
+            return Err(DecodeError::NaN);
+        }
+        if x.is_infinite() {
+            return Err(DecodeError::Infinite);
+        }
+        Ok(x)
+    }
+
+    fn f64(&mut self) -> Result<f64, DecodeError> {
+        let x = f64::from_le_bytes(self.take()?);
+        if x.is_nan() {
+            return Err(DecodeError::NaN);
+        }
+        if x.is_infinite() {
+            return Err(DecodeError::Infinite);
+        }
+        Ok(x)
+    }
+
+    fn vec3(&mut self) -> Result<Vec3, DecodeError> {
+        Ok(Vec3 {
+            x: self.f32()?,
+            y: self.f32()?,
+            z: self.f32()?,
+        })
+    }
+
+    fn transform(&mut self) -> Result<Transform, DecodeError> {
+        Ok(Transform {
+            x: self.vec3()?,
+            y: self.vec3()?,
+            z: self.vec3()?,
+            w: self.vec3()?,
+        })
+    }
+
+    fn param(&mut self) -> Result<Param, DecodeError> {
+        let base = self.f32()?;
+        let channel = match self.u8()? {
+            0 => None,
+            1 => {
+                let mut keyframes: Vec<Keyframe> = vec![];
+                for _ in 0..self.u32()? {
+                    let key = Keyframe {
+                        time: self.f32()?,
+                        value: self.f32()?,
+                        slope: self.f32()?,
+                        interpolation: match self.u8()? {
+                            0 => Interpolation::Step,
+                            1 => Interpolation::Linear,
+                            2 => Interpolation::Hermite,
+                            tag => return Err(DecodeError::UnknownInterpolationTag(tag)),
+                        },
+                    };
+                    if keyframes.last().is_some_and(|last| last.time > key.time) {
+                        return Err(DecodeError::UnsortedKeyframes);
+                    }
+                    keyframes.push(key);
+                }
+                Some(Channel::Keyframes(keyframes))
+            }
+            2 => Some(Channel::Linear(self.f32()?)),
+            3 => Some(Channel::Sine {
+                amplitude: self.f32()?,
+                frequency: self.f32()?,
+                phase: self.f32()?,
+            }),
+            tag => return Err(DecodeError::UnknownChannelTag(tag)),
+        };
+        Ok(Param { base, channel })
+    }
+}
+
+/// Check that the columns of a transformation's rotation and scale are orthogonal, and have the
+/// same positive length.
+fn is_similarity(t: Transform) -> bool {
+    let scale = t.scale();
+    let near = |x: f32, y: f32| (x - y).abs() <= NORMAL_TOLERANCE;
+    let [x, y, z] = [t.x, t.y, t.z].map(|v| v / scale);
+    scale > 0.0
+        && [x, y, z].into_iter().all(|v| near(v.length(), 1.0))
+        && near(x.dot(y), 0.0)
+        && near(y.dot(z), 0.0)
+        && near(z.dot(x), 0.0)
+}
+
+impl Sdf {
+    /// Encode this `Sdf` into the versioned binary format. Names are not encoded.
+    #[must_use]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut w = Writer(vec![]);
+        w.0.extend_from_slice(&MAGIC);
+        w.u32(VERSION);
+
+        w.index(self.materials.len());
+        for material in &self.materials {
+            match material {
+                Material::Flat(color) => {
+                    w.u8(0);
+                    w.f64(color.r);
+                    w.f64(color.g);
+                    w.f64(color.b);
+                    w.f64(color.a);
+                }
+            }
+        }
+
+        w.index(self.distances.len());
+        for distance in &self.distances {
+            match distance {
+                Distance::Sphere {
+                    center,
+                    radius,
+                    material,
+                } => {
+                    w.u8(0);
+                    w.vec3(*center);
+                    w.param(radius);
+                    w.index(*material);
+                }
+                Distance::Plane {
+                    normal,
+                    offset,
+                    material,
+                } => {
+                    w.u8(1);
+                    w.vec3(*normal);
+                    w.param(offset);
+                    w.index(*material);
+                }
+                Distance::Ocean {
+                    origin,
+                    waves,
+                    material,
+                } => {
+                    w.u8(16);
+                    w.vec3(*origin);
+                    w.index(waves.len());
+                    for wave in waves {
+                        w.vec3(wave.direction);
+                        w.f32(wave.amplitude);
+                        w.f32(wave.wavelength);
+                        w.f32(wave.speed);
+                    }
+                    w.index(*material);
+                }
+                Distance::Empty => w.u8(18),
+                Distance::Everything => w.u8(19),
+                Distance::Union(x, y) => {
+                    w.u8(2);
+                    w.index(*x);
+                    w.index(*y);
+                }
+                Distance::Intersection(x, y) => {
+                    w.u8(3);
+                    w.index(*x);
+                    w.index(*y);
+                }
+                Distance::Exclusion(x, y) => {
+                    w.u8(4);
+                    w.index(*x);
+                    w.index(*y);
+                }
+                Distance::Subtraction(x, y) => {
+                    w.u8(5);
+                    w.index(*x);
+                    w.index(*y);
+                }
+                Distance::Transform {
+                    child,
+                    inverse_transform,
+                    instance,
+                } => {
+                    w.u8(6);
+                    w.index(*child);
+                    w.transform(*inverse_transform);
+                    match instance {
+                        None => w.u8(0),
+                        Some(InstanceId(id)) => {
+                            w.u8(1);
+                            w.u32(*id);
+                        }
+                    }
+                }
+                Distance::Repeat {
+                    child,
+                    period,
+                    counts,
+                } => {
+                    w.u8(7);
+                    w.index(*child);
+                    w.vec3(*period);
+                    match counts {
+                        None => w.u8(0),
+                        Some(counts) => {
+                            w.u8(1);
+                            for count in counts {
+                                w.u32(*count);
+                            }
+                        }
+                    }
+                }
+                Distance::Mirror {
+                    child,
+                    normal,
+                    offset,
+                } => {
+                    w.u8(8);
+                    w.index(*child);
+                    w.vec3(*normal);
+                    w.param(offset);
+                }
+                Distance::Symmetric { child, axes } => {
+                    w.u8(9);
+                    w.index(*child);
+                    w.u8(axes_mask(*axes));
+                }
+                Distance::Twist {
+                    child,
+                    axis,
+                    along,
+                    rate,
+                } => {
+                    w.u8(10);
+                    w.index(*child);
+                    w.vec3(*axis);
+                    w.vec3(*along);
+                    w.param(rate);
+                }
+                Distance::Taper {
+                    child,
+                    axis,
+                    factor,
+                } => {
+                    w.u8(11);
+                    w.index(*child);
+                    w.vec3(*axis);
+                    w.param(factor);
+                }
+                Distance::Rotate { child, axis, angle } => {
+                    w.u8(17);
+                    w.index(*child);
+                    w.vec3(*axis);
+                    w.param(angle);
+                }
+                Distance::Elongate { child, extent } => {
+                    w.u8(12);
+                    w.index(*child);
+                    w.vec3(*extent);
+                }
+                Distance::Offset { child, amount } => {
+                    w.u8(13);
+                    w.index(*child);
+                    w.param(amount);
+                }
+                Distance::Shell { child, thickness } => {
+                    w.u8(14);
+                    w.index(*child);
+                    w.param(thickness);
+                }
+                Distance::Displace {
+                    child,
+                    noise: Noise::Gradient(seed),
+                    amplitude,
+                    frequency,
+                    octaves,
+                } => {
+                    w.u8(15);
+                    w.index(*child);
+                    w.u8(0);
+                    w.u32(*seed);
+                    w.param(amplitude);
+                    w.f32(*frequency);
+                    w.u32(*octaves);
+                }
+            }
+        }
+
+        w.0
+    }
+
+    /// Decode an `Sdf` from the versioned binary format, checking all of its invariants.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Sdf, DecodeError> {
+        let mut r = Reader(bytes);
+        if r.take::<4>()? != MAGIC {
+            return Err(DecodeError::BadMagic);
+        }
+        match r.u32()? {
+            VERSION => {}
+            version => return Err(DecodeError::UnsupportedVersion(version)),
+        }
+
+        let mut materials = vec![];
+        for _ in 0..r.u32()? {
+            materials.push(match r.u8()? {
+                0 => Material::Flat(Color {
+                    r: r.f64()?,
+                    g: r.f64()?,
+                    b: r.f64()?,
+                    a: r.f64()?,
+                }),
+                tag => return Err(DecodeError::UnknownMaterialTag(tag)),
+            });
+        }
+
+        let mut distances = vec![];
+        for node in 0..r.u32()? as usize {
+            let check_child = |child: usize| {
+                if child < node {
+                    Ok(child)
+                } else {
+                    Err(DecodeError::ForwardReference { node, child })
+                }
+            };
+            let check_normal = |normal: Vec3| {
+                if (normal.length() - 1.0).abs() <= NORMAL_TOLERANCE {
+                    Ok(normal)
+                } else {
+                    Err(DecodeError::UnnormalizedNormal { node })
+                }
+            };
+            let check_material = |material: usize| {
+                if material < materials.len() {
+                    Ok(material)
+                } else {
+                    Err(DecodeError::MissingMaterial { node, material })
+                }
+            };
+            distances.push(match r.u8()? {
+                0 => Distance::Sphere {
+                    center: r.vec3()?,
+                    radius: r.param()?,
+                    material: check_material(r.index()?)?,
+                },
+                1 => Distance::Plane {
+                    normal: check_normal(r.vec3()?)?,
+                    offset: r.param()?,
+                    material: check_material(r.index()?)?,
+                },
+                2 => Distance::Union(check_child(r.index()?)?, check_child(r.index()?)?),
+                3 => Distance::Intersection(check_child(r.index()?)?, check_child(r.index()?)?),
+                4 => Distance::Exclusion(check_child(r.index()?)?, check_child(r.index()?)?),
+                5 => Distance::Subtraction(check_child(r.index()?)?, check_child(r.index()?)?),
+                6 => {
+                    let child = check_child(r.index()?)?;
+                    let inverse_transform = r.transform()?;
+                    if !is_similarity(inverse_transform) {
+                        return Err(DecodeError::NotSimilarity { node });
+                    }
+                    let instance = match r.u8()? {
+                        0 => None,
+                        1 => Some(InstanceId(r.u32()?)),
+                        tag => return Err(DecodeError::UnknownOptionTag { node, tag }),
+                    };
+                    Distance::Transform {
+                        child,
+                        inverse_transform,
+                        instance,
+                    }
+                }
+                7 => {
+                    let child = check_child(r.index()?)?;
+                    let period = r.vec3()?;
+                    let counts = match r.u8()? {
+                        0 => None,
+                        1 => Some([r.u32()?, r.u32()?, r.u32()?]),
+                        tag => return Err(DecodeError::UnknownOptionTag { node, tag }),
+                    };
+                    if period.reduce(f32::min) < 0.0
+                        || counts.is_some_and(|counts| counts.contains(&0))
+                    {
+                        return Err(DecodeError::InvalidRepetition { node });
+                    }
+                    Distance::Repeat {
+                        child,
+                        period,
+                        counts,
+                    }
+                }
+                8 => Distance::Mirror {
+                    child: check_child(r.index()?)?,
+                    normal: check_normal(r.vec3()?)?,
+                    offset: r.param()?,
+                },
+                9 => {
+                    let child = check_child(r.index()?)?;
+                    let mask = r.u8()?;
+                    if mask >> 3 != 0 {
+                        return Err(DecodeError::InvalidAxes { node });
+                    }
+                    Distance::Symmetric {
+                        child,
+                        axes: [0, 1, 2].map(|axis| mask >> axis & 1 == 1),
+                    }
+                }
+                10 => {
+                    let child = check_child(r.index()?)?;
+                    let axis = check_normal(r.vec3()?)?;
+                    let along = check_normal(r.vec3()?)?;
+                    Distance::Twist {
+                        child,
+                        axis,
+                        along,
+                        rate: r.param()?,
+                    }
+                }
+                11 => Distance::Taper {
+                    child: check_child(r.index()?)?,
+                    axis: check_normal(r.vec3()?)?,
+                    factor: r.param()?,
+                },
+                12 => {
+                    let child = check_child(r.index()?)?;
+                    let extent = r.vec3()?;
+                    if !is_valid_extent(extent) {
+                        return Err(DecodeError::InvalidExtent { node });
+                    }
+                    Distance::Elongate { child, extent }
+                }
+                13 => Distance::Offset {
+                    child: check_child(r.index()?)?,
+                    amount: r.param()?,
+                },
+                14 => Distance::Shell {
+                    child: check_child(r.index()?)?,
+                    thickness: r.param()?,
+                },
+                15 => Distance::Displace {
+                    child: check_child(r.index()?)?,
+                    noise: match r.u8()? {
+                        0 => Noise::Gradient(r.u32()?),
+                        tag => return Err(DecodeError::UnknownNoiseTag(tag)),
+                    },
+                    amplitude: r.param()?,
+                    frequency: r.f32()?,
+                    octaves: r.u32()?,
+                },
+                16 => {
+                    let origin = r.vec3()?;
+                    let mut waves = vec![];
+                    for _ in 0..r.u32()? {
+                        let wave = Wave {
+                            direction: check_normal(r.vec3()?)?,
+                            amplitude: r.f32()?,
+                            wavelength: r.f32()?,
+                            speed: r.f32()?,
+                        };
+                        if wave.direction.y != 0.0 || wave.wavelength <= 0.0 {
+                            return Err(DecodeError::InvalidWave { node });
+                        }
+                        waves.push(wave);
+                    }
+                    Distance::Ocean {
+                        origin,
+                        waves,
+                        material: check_material(r.index()?)?,
+                    }
+                }
+                17 => Distance::Rotate {
+                    child: check_child(r.index()?)?,
+                    axis: check_normal(r.vec3()?)?,
+                    angle: r.param()?,
+                },
+                18 => Distance::Empty,
+                19 => Distance::Everything,
+                tag => return Err(DecodeError::UnknownDistanceTag(tag)),
+            });
+        }
+
+        if !r.0.is_empty() {
+            return Err(DecodeError::TrailingBytes);
+        }
+        if distances.is_empty() {
+            return Err(DecodeError::Empty);
+        }
+
+        Ok(Sdf {
+            distances,
+            materials,
+            names: vec![],
+        })
+    }
+
+    /// Get a hash of the encoded form of this `Sdf`, which is stable across machines and runs.
+    /// `Sdf`s with bitwise-equal parameters have equal hashes, so this can be used for deduplication.
+    #[must_use]
+    pub fn content_hash(&self) -> u64 {
+        fnv1a(&self.to_bytes())
+    }
+}
+
+/// The 64-bit FNV-1a hash, chosen because it is simple and does not depend on the platform.
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in bytes {
+        hash ^= u64::from(*byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    fn ball() -> Sdf {
+        Sdf::sphere(1.0, Material::Flat(Color::WHITE))
+    }
+
+    fn vec3(x: f32, y: f32, z: f32) -> Vec3 {
+        Vec3 { x, y, z }
+    }
+
+    /// The offset of the first distance's tag in an encoding with one material: the header, the
+    /// material count, the material's tag and color, and the distance count.
+    const FIRST_DISTANCE: usize = 4 + 4 + 4 + 1 + 4 * 8 + 4;
+
+    /// The length of `ball()`'s sphere: its tag, center, radius without a channel, and material.
+    const SPHERE_LEN: usize = 1 + 12 + 4 + 1 + 4;
+
+    /// Get one `Sdf` for each kind of node, with every kind of channel and optional field.
+    fn every_kind() -> Vec<Sdf> {
+        let white = || Material::Flat(Color::WHITE);
+        let keyframes = Channel::Keyframes(vec![
+            Keyframe {
+                time: 0.0,
+                value: 1.0,
+                slope: 0.0,
+                interpolation: Interpolation::Step,
+            },
+            Keyframe {
+                time: 1.0,
+                value: 2.0,
+                slope: 0.5,
+                interpolation: Interpolation::Hermite,
+            },
+        ]);
+        let sine = Channel::Sine {
+            amplitude: 0.5,
+            frequency: 2.0,
+            phase: 1.0,
+        };
+        let waves = [Wave {
+            direction: X,
+            amplitude: 0.2,
+            wavelength: 3.0,
+            speed: 1.0,
+        }];
+        let other = || Sdf::sphere(keyframes.clone(), Material::Flat(Color::BLACK)).translate(X);
+        vec![
+            Sdf::sphere(Channel::Linear(0.5), white()),
+            Sdf::plane(Y, sine.clone(), white()),
+            Sdf::ocean(&waves, white()),
+            Sdf::empty(),
+            Sdf::everything(),
+            ball().union(other()),
+            ball().intersect(other()),
+            ball().exclude(other()),
+            ball().subtract(other()),
+            ball().scale(2.0),
+            ball().instance(InstanceId(7), Transform::translation(Y)),
+            ball().repeat(vec3(4.0, 0.0, 4.0)),
+            ball().repeat_limited(X * 3.0, [2, 1, 1]),
+            ball().mirror(X, 0.5),
+            ball().symmetric([true, false, true]),
+            ball().twist(Y, 0.5),
+            ball().bend(Y, X, 0.25),
+            ball().taper(Y, sine),
+            ball().rotate(Y, Channel::Linear(1.0)),
+            ball().elongate(vec3(1.0, 0.0, 0.5)),
+            ball().offset(0.25),
+            ball().shell(0.1),
+            ball().displace(Noise::Gradient(3), 0.1, 2.0, 3),
+        ]
+    }
+
+    #[test]
+    fn round_trips_every_kind_of_node() {
+        let mut kinds = HashSet::new();
+        for sdf in every_kind() {
+            let bytes = sdf.to_bytes();
+            let decoded = Sdf::from_bytes(&bytes).unwrap();
+            assert_eq!(decoded.to_bytes(), bytes, "{sdf}");
+            assert_eq!(decoded.content_hash(), sdf.content_hash());
+            kinds.extend(decoded.distances.iter().map(std::mem::discriminant));
+        }
+        assert_eq!(kinds.len(), 20, "a kind of node is missing");
+    }
+
+    #[test]
+    fn content_hash_is_unchanged() {
+        // caches and peers compare hashes, so this may only change along with `VERSION`
+        let sdf = ball().union(ball().translate(X).offset(0.5));
+        assert_eq!(sdf.content_hash(), 0xea53_8e3c_ecb5_1bd3);
+    }
+
+    #[test]
+    fn rejects_bad_headers() {
+        let mut bytes = ball().to_bytes();
+        bytes[0] = b'X';
+        assert_eq!(Sdf::from_bytes(&bytes).err(), Some(DecodeError::BadMagic));
+
+        let mut bytes = ball().to_bytes();
+        bytes[4..8].copy_from_slice(&(VERSION + 1).to_le_bytes());
+        assert_eq!(
+            Sdf::from_bytes(&bytes).err(),
+            Some(DecodeError::UnsupportedVersion(VERSION + 1))
+        );
+    }
+
+    #[test]
+    fn rejects_an_unknown_tag() {
+        let mut bytes = ball().to_bytes();
+        bytes[FIRST_DISTANCE] = 99;
+        assert_eq!(
+            Sdf::from_bytes(&bytes).err(),
+            Some(DecodeError::UnknownDistanceTag(99))
+        );
+    }
+
+    #[test]
+    fn rejects_self_and_forward_references() {
+        let bytes = ball().offset(0.5).to_bytes();
+        let child = FIRST_DISTANCE + SPHERE_LEN + 1;
+        for index in [1u32, 2] {
+            let mut bytes = bytes.clone();
+            bytes[child..child + 4].copy_from_slice(&index.to_le_bytes());
+            assert_eq!(
+                Sdf::from_bytes(&bytes).err(),
+                Some(DecodeError::ForwardReference {
+                    node: 1,
+                    child: index as usize
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_nan_and_infinite_parameters() {
+        let radius = FIRST_DISTANCE + 1 + 12;
+        for (value, error) in [
+            (f32::NAN, DecodeError::NaN),
+            (f32::INFINITY, DecodeError::Infinite),
+            (f32::NEG_INFINITY, DecodeError::Infinite),
+        ] {
+            let mut bytes = ball().to_bytes();
+            bytes[radius..radius + 4].copy_from_slice(&value.to_le_bytes());
+            assert_eq!(Sdf::from_bytes(&bytes).err(), Some(error));
+        }
+    }
+
+    #[test]
+    fn rejects_an_unnormalized_normal() {
+        let mut bytes = Sdf::plane(Y, 0.0, Material::Flat(Color::WHITE)).to_bytes();
+        let normal_y = FIRST_DISTANCE + 1 + 4;
+        bytes[normal_y..normal_y + 4].copy_from_slice(&2.0f32.to_le_bytes());
+        assert_eq!(
+            Sdf::from_bytes(&bytes).err(),
+            Some(DecodeError::UnnormalizedNormal { node: 0 })
+        );
+    }
+
+    #[test]
+    fn rejects_a_transformation_that_is_not_a_similarity() {
+        let mut bytes = ball().scale(2.0).to_bytes();
+        let transform = FIRST_DISTANCE + SPHERE_LEN;
+        assert_eq!(bytes[transform], 6, "the scale is a transform node");
+        // shear the image of `X` towards `Y`
+        let x_y = transform + 1 + 4 + 4;
+        bytes[x_y..x_y + 4].copy_from_slice(&1.0f32.to_le_bytes());
+        assert_eq!(
+            Sdf::from_bytes(&bytes).err(),
+            Some(DecodeError::NotSimilarity { node: 1 })
+        );
+    }
+
+    #[test]
+    fn rejects_truncated_input_and_trailing_bytes() {
+        let mut bytes = ball().offset(0.5).to_bytes();
+        for len in 0..bytes.len() {
+            assert_eq!(
+                Sdf::from_bytes(&bytes[..len]).err(),
+                Some(DecodeError::UnexpectedEnd),
+                "{len} bytes"
+            );
+        }
+        bytes.push(0);
+        assert_eq!(
+            Sdf::from_bytes(&bytes).err(),
+            Some(DecodeError::TrailingBytes)
+        );
+    }
+
+    #[test]
+    fn rejects_a_negative_extent() {
+        let mut bytes = ball().elongate(X).to_bytes();
+        // the extent is the last thing in the encoding
+        let len = bytes.len();
+        bytes[len - 12..len - 8].copy_from_slice(&(-1.0f32).to_le_bytes());
+        assert_eq!(
+            Sdf::from_bytes(&bytes).err(),
+            Some(DecodeError::InvalidExtent { node: 1 })
+        );
+    }
+
+    #[test]
+    fn round_trips_planes_and_mirrors_made_with_unnormalized_normals() {
+        let normal = Vec3 {
+            x: 2.0,
+            y: 0.0,
+            z: 0.0,
+        };
+        let plane = Sdf::plane(normal, 1.0, Material::Flat(Color::WHITE));
+        let mirror = ball().mirror(normal, 0.0);
+        for sdf in [plane, mirror] {
+            let bytes = sdf.to_bytes();
+            assert_eq!(Sdf::from_bytes(&bytes).unwrap().to_bytes(), bytes);
+        }
+    }
+}
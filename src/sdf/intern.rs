@@ -0,0 +1,537 @@
+//! Hash-consing support for `Sdf::simplify`.
+//!
+//! Floats are hashed by their bits, with `-0.0` hashed as `0.0` so that hashing agrees with `==`.
+//! Values that contain a NaN are never equal to anything, so they are never interned.
+
+use super::*;
+use std::hash::{Hash, Hasher};
+
+fn hash_f32(x: f32, state: &mut impl Hasher) {
+    let x = if x == 0.0 { 0.0 } else { x };
+    x.to_bits().hash(state);
+}
+
+fn hash_f64(x: f64, state: &mut impl Hasher) {
+    let x = if x == 0.0 { 0.0 } else { x };
+    x.to_bits().hash(state);
+}
+
+fn hash_vec3(v: Vec3, state: &mut impl Hasher) {
+    hash_f32(v.x, state);
+    hash_f32(v.y, state);
+    hash_f32(v.z, state);
+}
+
+fn hash_transform(t: Transform, state: &mut impl Hasher) {
+    hash_vec3(t.x, state);
+    hash_vec3(t.y, state);
+    hash_vec3(t.z, state);
+    hash_vec3(t.w, state);
+}
+
+fn hash_param(param: &Param, state: &mut impl Hasher) {
+    hash_f32(param.base, state);
+    param.channel.is_some().hash(state);
+    if let Some(channel) = &param.channel {
+        std::mem::discriminant(channel).hash(state);
+        match channel {
+            Channel::Keyframes(keyframes) => {
+                keyframes.len().hash(state);
+                for key in keyframes {
+                    hash_f32(key.time, state);
+                    hash_f32(key.value, state);
+                    hash_f32(key.slope, state);
+                    key.interpolation.hash(state);
+                }
+            }
+            Channel::Linear(rate) => hash_f32(*rate, state),
+            Channel::Sine {
+                amplitude,
+                frequency,
+                phase,
+            } => {
+                hash_f32(*amplitude, state);
+                hash_f32(*frequency, state);
+                hash_f32(*phase, state);
+            }
+        }
+    }
+}
+
+fn vec3_has_nan(v: Vec3) -> bool {
+    v.x.is_nan() || v.y.is_nan() || v.z.is_nan()
+}
+
+fn param_has_nan(param: &Param) -> bool {
+    let channel_has_nan = |channel: &Channel| match channel {
+        Channel::Keyframes(keyframes) => keyframes
+            .iter()
+            .any(|key| key.time.is_nan() || key.value.is_nan() || key.slope.is_nan()),
+        Channel::Linear(rate) => rate.is_nan(),
+        Channel::Sine {
+            amplitude,
+            frequency,
+            phase,
+        } => amplitude.is_nan() || frequency.is_nan() || phase.is_nan(),
+    };
+    param.base.is_nan() || param.channel.as_ref().is_some_and(channel_has_nan)
+}
+
+impl Hash for Distance {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            Distance::Sphere {
+                center,
+                radius,
+                material,
+            } => {
+                hash_vec3(*center, state);
+                hash_param(radius, state);
+                material.hash(state);
+            }
+            Distance::Plane {
+                normal,
+                offset,
+                material,
+            } => {
+                hash_vec3(*normal, state);
+                hash_param(offset, state);
+                material.hash(state);
+            }
+            Distance::Ocean {
+                origin,
+                waves,
+                material,
+            } => {
+                hash_vec3(*origin, state);
+                waves.len().hash(state);
+                for wave in waves {
+                    hash_vec3(wave.direction, state);
+                    hash_f32(wave.amplitude, state);
+                    hash_f32(wave.wavelength, state);
+                    hash_f32(wave.speed, state);
+                }
+                material.hash(state);
+            }
+            Distance::Empty | Distance::Everything => {}
+            Distance::Union(x, y)
+            | Distance::Intersection(x, y)
+            | Distance::Exclusion(x, y)
+            | Distance::Subtraction(x, y) => {
+                x.hash(state);
+                y.hash(state);
+            }
+            Distance::Transform {
+                child,
+                inverse_transform,
+                instance,
+            } => {
+                child.hash(state);
+                hash_transform(*inverse_transform, state);
+                instance.hash(state);
+            }
+            Distance::Repeat {
+                child,
+                period,
+                counts,
+            } => {
+                child.hash(state);
+                hash_vec3(*period, state);
+                counts.hash(state);
+            }
+            Distance::Mirror {
+                child,
+                normal,
+                offset,
+            } => {
+                child.hash(state);
+                hash_vec3(*normal, state);
+                hash_param(offset, state);
+            }
+            Distance::Symmetric { child, axes } => {
+                child.hash(state);
+                axes.hash(state);
+            }
+            Distance::Twist {
+                child,
+                axis,
+                along,
+                rate,
+            } => {
+                child.hash(state);
+                hash_vec3(*axis, state);
+                hash_vec3(*along, state);
+                hash_param(rate, state);
+            }
+            Distance::Taper {
+                child,
+                axis,
+                factor: x,
+            }
+            | Distance::Rotate {
+                child,
+                axis,
+                angle: x,
+            } => {
+                child.hash(state);
+                hash_vec3(*axis, state);
+                hash_param(x, state);
+            }
+            Distance::Elongate { child, extent } => {
+                child.hash(state);
+                hash_vec3(*extent, state);
+            }
+            Distance::Offset { child, amount: x }
+            | Distance::Shell {
+                child,
+                thickness: x,
+            } => {
+                child.hash(state);
+                hash_param(x, state);
+            }
+            Distance::Displace {
+                child,
+                noise,
+                amplitude,
+                frequency,
+                octaves,
+            } => {
+                child.hash(state);
+                noise.hash(state);
+                hash_param(amplitude, state);
+                hash_f32(*frequency, state);
+                octaves.hash(state);
+            }
+        }
+    }
+}
+
+impl Hash for Material {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            Material::Flat(color) => {
+                hash_f64(color.r, state);
+                hash_f64(color.g, state);
+                hash_f64(color.b, state);
+                hash_f64(color.a, state);
+            }
+        }
+    }
+}
+
+/// Values that can be interned, as long as they are equal to themselves.
+pub(super) trait Internable: Clone + PartialEq + Hash {
+    /// Check whether this value contains a NaN, which would make it unequal to itself.
+    fn has_nan(&self) -> bool;
+}
+
+impl Internable for Distance {
+    fn has_nan(&self) -> bool {
+        match self {
+            Distance::Sphere {
+                center: v,
+                radius: w,
+                ..
+            }
+            | Distance::Plane {
+                normal: v,
+                offset: w,
+                ..
+            } => vec3_has_nan(*v) || param_has_nan(w),
+            Distance::Ocean { origin, waves, .. } => {
+                vec3_has_nan(*origin)
+                    || waves.iter().any(|wave| {
+                        vec3_has_nan(wave.direction)
+                            || [wave.amplitude, wave.wavelength, wave.speed]
+                                .into_iter()
+                                .any(f32::is_nan)
+                    })
+            }
+            Distance::Empty
+            | Distance::Everything
+            | Distance::Union(..)
+            | Distance::Intersection(..)
+            | Distance::Exclusion(..)
+            | Distance::Subtraction(..)
+            | Distance::Symmetric { .. } => false,
+            Distance::Transform {
+                inverse_transform: t,
+                ..
+            } => [t.x, t.y, t.z, t.w].into_iter().any(vec3_has_nan),
+            Distance::Repeat { period, .. } => vec3_has_nan(*period),
+            Distance::Mirror { normal, offset, .. } => {
+                vec3_has_nan(*normal) || param_has_nan(offset)
+            }
+            Distance::Twist {
+                axis, along, rate, ..
+            } => vec3_has_nan(*axis) || vec3_has_nan(*along) || param_has_nan(rate),
+            Distance::Taper {
+                axis, factor: x, ..
+            }
+            | Distance::Rotate { axis, angle: x, .. } => vec3_has_nan(*axis) || param_has_nan(x),
+            Distance::Elongate { extent, .. } => vec3_has_nan(*extent),
+            Distance::Offset { amount: x, .. } | Distance::Shell { thickness: x, .. } => {
+                param_has_nan(x)
+            }
+            Distance::Displace {
+                amplitude,
+                frequency,
+                ..
+            } => param_has_nan(amplitude) || frequency.is_nan(),
+        }
+    }
+}
+
+impl Internable for Material {
+    fn has_nan(&self) -> bool {
+        match self {
+            Material::Flat(color) => [color.r, color.g, color.b, color.a]
+                .into_iter()
+                .any(f64::is_nan),
+        }
+    }
+}
+
+/// A wrapper that is only constructed around values without NaNs, which makes `==` reflexive.
+#[derive(PartialEq)]
+struct Key<T>(T);
+
+impl<T: PartialEq> Eq for Key<T> {}
+
+impl<T: Hash> Hash for Key<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash(state);
+    }
+}
+
+/// Deduplicates values, keeping the first occurrence of each one.
+pub(super) struct Interner<T> {
+    pub(super) values: Vec<T>,
+    indices: HashMap<Key<T>, usize>,
+}
+
+impl<T: Internable> Interner<T> {
+    pub(super) fn new() -> Interner<T> {
+        Interner {
+            values: vec![],
+            indices: HashMap::new(),
+        }
+    }
+
+    /// Add a value without merging it with any equal values, now or later.
+    pub(super) fn push(&mut self, value: T) -> usize {
+        self.values.push(value);
+        self.values.len() - 1
+    }
+
+    /// Get the index of a value equal to this one, adding it if there isn't one already.
+    pub(super) fn intern(&mut self, value: T) -> usize {
+        if value.has_nan() {
+            return self.push(value);
+        }
+        let len = self.values.len();
+        let index = *self.indices.entry(Key(value.clone())).or_insert(len);
+        if index == len {
+            self.values.push(value);
+        }
+        index
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `Sdf::simplify` as it was before hash-consing, which finds equal nodes and materials by
+    /// searching everything kept so far, with the same folds and dead node removal.
+    fn quadratic_simplify(sdf: Sdf) -> Sdf {
+        let mut materials: Vec<Material> = vec![];
+        let material_map: Vec<usize> = sdf
+            .materials
+            .into_iter()
+            .map(|material| {
+                materials
+                    .iter()
+                    .position(|x| *x == material)
+                    .unwrap_or_else(|| {
+                        materials.push(material);
+                        materials.len() - 1
+                    })
+            })
+            .collect();
+
+        let mut named = vec![false; sdf.distances.len()];
+        for (_, node) in &sdf.names {
+            named[*node] = true;
+        }
+
+        // named nodes are never merged, in either direction
+        let mut distances: Vec<Distance> = vec![];
+        let mut kept_named: Vec<bool> = vec![];
+        let mut distance_map: Vec<usize> = vec![];
+        for (i, mut distance) in sdf.distances.into_iter().enumerate() {
+            distance.map_material(|material| material_map[material]);
+            distance.map_children(|child| distance_map[child]);
+            if !named[i] {
+                match distance.fold(&distances) {
+                    Some(Folded::Node(node)) => {
+                        distance_map.push(node);
+                        continue;
+                    }
+                    Some(Folded::Empty) => distance = Distance::Empty,
+                    Some(Folded::Everything) => distance = Distance::Everything,
+                    None => {}
+                }
+                let existing =
+                    (0..distances.len()).position(|j| !kept_named[j] && distances[j] == distance);
+                if let Some(j) = existing {
+                    distance_map.push(j);
+                    continue;
+                }
+            }
+            distances.push(distance);
+            kept_named.push(named[i]);
+            distance_map.push(distances.len() - 1);
+        }
+
+        let root = *distance_map.last().unwrap();
+        Sdf {
+            distances,
+            materials,
+            names: sdf
+                .names
+                .into_iter()
+                .map(|(name, node)| (name, distance_map[node]))
+                .collect(),
+        }
+        .without_dead_nodes(root)
+    }
+
+    /// Pick one of `values` at random.
+    fn pick<T: Clone>(rng: &mut Rng, values: &[T]) -> T {
+        values[rng.next_u64() as usize % values.len()].clone()
+    }
+
+    /// Build a random DAG from a few values, so that many nodes and materials are equal, with
+    /// shared children, `Empty` and `Everything` nodes to fold, nodes that the root can't reach,
+    /// and some names. With `nan`, some of the values are NaN.
+    fn random_sdf(rng: &mut Rng, nan: bool) -> Sdf {
+        let mut numbers = vec![0.0, -0.0, 0.5, 1.0];
+        if nan {
+            numbers.push(f32::NAN);
+        }
+        let colors = [Color::RED, Color::GREEN, Color::WHITE];
+
+        let materials: Vec<Material> = (0..=rng.next_u64() % 5)
+            .map(|_| Material::Flat(pick(rng, &colors)))
+            .collect();
+        let mut distances: Vec<Distance> = vec![];
+        for _ in 0..=rng.next_u64() % 40 {
+            let len = distances.len();
+            let mut child = || rng.next_u64() as usize % len.max(1);
+            let (x, y) = (child(), child());
+            let distance = match rng.next_u64() % if len == 0 { 3 } else { 10 } {
+                0 => Distance::Sphere {
+                    center: X * pick(rng, &numbers),
+                    radius: pick(rng, &numbers).into(),
+                    material: rng.next_u64() as usize % materials.len(),
+                },
+                1 => Distance::Empty,
+                2 => Distance::Everything,
+                3 => Distance::Union(x, y),
+                4 => Distance::Intersection(x, y),
+                5 => Distance::Exclusion(x, y),
+                6 => Distance::Subtraction(x, y),
+                7 => Distance::Offset {
+                    child: x,
+                    amount: pick(rng, &numbers).into(),
+                },
+                8 => Distance::Shell {
+                    child: x,
+                    thickness: pick(rng, &numbers).into(),
+                },
+                _ => Distance::Transform {
+                    child: x,
+                    inverse_transform: Transform::translation(Y * pick(rng, &numbers)),
+                    instance: None,
+                },
+            };
+            distances.push(distance);
+        }
+
+        let names = (0..distances.len())
+            .filter(|_| rng.next_u64().is_multiple_of(8))
+            .map(|node| (format!("node{node}"), node))
+            .collect();
+        Sdf {
+            distances,
+            materials,
+            names,
+        }
+    }
+
+    /// Check that two SDFs have the same nodes, materials and names, comparing their encodings so
+    /// that floats are compared by their bits, and NaNs can be compared too.
+    fn assert_same(actual: &Sdf, expected: &Sdf, seed: u64) {
+        assert!(
+            actual.to_bytes() == expected.to_bytes(),
+            "seed {seed}: got\n{actual}\nexpected\n{expected}"
+        );
+        assert_eq!(actual.names, expected.names, "seed {seed}: names");
+    }
+
+    #[test]
+    fn simplify_matches_quadratic_dedup() {
+        for seed in 0..1000 {
+            let mut rng = Rng::new(seed);
+            let sdf = random_sdf(&mut rng, false);
+            assert_same(&sdf.clone().simplify(), &quadratic_simplify(sdf), seed);
+        }
+    }
+
+    #[test]
+    fn simplify_matches_quadratic_dedup_with_nan() {
+        for seed in 0..1000 {
+            let mut rng = Rng::new(seed);
+            let sdf = random_sdf(&mut rng, true);
+            assert_same(&sdf.clone().simplify(), &quadratic_simplify(sdf), seed);
+        }
+    }
+
+    #[test]
+    fn simplify_merges_shared_nodes_and_drops_dead_ones() {
+        let sphere = || Distance::Sphere {
+            center: ORIGIN,
+            radius: 1.0.into(),
+            material: 0,
+        };
+        let sdf = Sdf {
+            distances: vec![
+                sphere(),
+                Distance::Sphere {
+                    center: X * -0.0,
+                    radius: 1.0.into(),
+                    material: 1,
+                },
+                Distance::Everything, // unreachable
+                Distance::Empty,
+                Distance::Union(0, 3),
+                Distance::Union(1, 4),
+            ],
+            materials: vec![Material::Flat(Color::RED), Material::Flat(Color::RED)],
+            names: vec![],
+        };
+        let simplified = sdf.simplify();
+        assert_same(
+            &simplified,
+            &Sdf {
+                distances: vec![sphere(), Distance::Union(0, 0)],
+                materials: vec![Material::Flat(Color::RED)],
+                names: vec![],
+            },
+            0,
+        );
+    }
+}
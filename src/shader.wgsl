@@ -16,6 +16,7 @@ struct Uniforms {
 	min_dist: f32,
 	max_dist: f32,
 	max_iter: u32,
+	lipschitz: f32, // steps are divided by this so that they don't overshoot
 
 	camera: Camera,
 }
@@ -29,7 +30,7 @@ struct Camera {
 var<uniform> uniforms: Uniforms;
 
 struct Distance {
-	tag: u32, // Sphere, Plane, Union, Intersection, Exclusion, Subtraction
+	tag: u32, // Sphere, Plane, Union, Intersection, Exclusion, Subtraction, Transform, Repeat, Mirror, Symmetric, Twist, Taper, Elongate, Offset, Shell, Displace, Ocean, Rotate, Empty, Everything
 	x: u32,
 	y: u32,
 	v: vec4<f32>,
@@ -48,57 +49,324 @@ struct Material {
 @group(0) @binding(2)
 var<storage> materials: array<Material>;
 
+@group(0) @binding(3)
+var<storage> parameters: array<vec4<f32>>;
+
 struct DistanceResult {
 	distance: f32,
 	material: u32,
 }
 
+// The DAG is traversed depth-first with an explicit stack, since WGSL has no recursion.
+// Each frame evaluates one node at one point, so shared nodes can be evaluated at many points.
+const MAX_DEPTH: u32 = 64u; // assert: equal to `MAX_DEPTH` in `sdf.rs`
+
+struct Frame {
+	node: u32,
+	phase: u32, // how many children have been evaluated so far
+	point: vec3<f32>,
+}
+
+// Gradient noise, which must match `Noise` in `math.rs` operation for operation,
+// so that the CPU and GPU agree bit for bit.
+fn noise_hash(x: u32, y: u32, z: u32, seed: u32) -> u32 {
+	var h = seed ^ (x * 0x8da6b343u) ^ (y * 0xd8163841u) ^ (z * 0xcb1ab31fu);
+	h ^= h >> 16u;
+	h *= 0x85ebca6bu;
+	h ^= h >> 13u;
+	h *= 0xc2b2ae35u;
+	h ^= h >> 16u;
+	return h;
+}
+
+fn noise_gradient(hash: u32, offset: vec3<f32>) -> f32 {
+	let h = hash & 15u;
+	let u = select(offset.y, offset.x, h < 8u);
+	var v = offset.z;
+	if h < 4u { v = offset.y; } else if h == 12u || h == 14u { v = offset.x; }
+	return select(-u, u, (h & 1u) == 0u) + select(-v, v, (h & 2u) == 0u);
+}
+
+fn noise_corner(lattice: vec3<u32>, local: vec3<f32>, seed: u32, corner: vec3<u32>) -> f32 {
+	let l = lattice + corner;
+	return noise_gradient(noise_hash(l.x, l.y, l.z, seed), local - vec3<f32>(corner));
+}
+
+fn noise_lerp(a: f32, b: f32, t: f32) -> f32 {
+	return a + t * (b - a); // not `mix`, which may round differently
+}
+
+fn gradient_noise(p: vec3<f32>, seed: u32) -> f32 {
+	let cell = floor(p);
+	let local = p - cell;
+	let lattice = bitcast<vec3<u32>>(vec3<i32>(cell));
+	let t = local;
+	let fade = t * t * t * (t * (t * 6.0 - 15.0) + 10.0);
+	return noise_lerp(
+		noise_lerp(
+			noise_lerp(noise_corner(lattice, local, seed, vec3<u32>(0u, 0u, 0u)), noise_corner(lattice, local, seed, vec3<u32>(1u, 0u, 0u)), fade.x),
+			noise_lerp(noise_corner(lattice, local, seed, vec3<u32>(0u, 1u, 0u)), noise_corner(lattice, local, seed, vec3<u32>(1u, 1u, 0u)), fade.x),
+			fade.y,
+		),
+		noise_lerp(
+			noise_lerp(noise_corner(lattice, local, seed, vec3<u32>(0u, 0u, 1u)), noise_corner(lattice, local, seed, vec3<u32>(1u, 0u, 1u)), fade.x),
+			noise_lerp(noise_corner(lattice, local, seed, vec3<u32>(0u, 1u, 1u)), noise_corner(lattice, local, seed, vec3<u32>(1u, 1u, 1u)), fade.x),
+			fade.y,
+		),
+		fade.z,
+	);
+}
+
+fn fbm(p: vec3<f32>, seed: u32, octaves: u32) -> f32 {
+	var sum = 0.0;
+	var scale = 1.0;
+	for (var octave = 0u; octave < octaves; octave++) {
+		sum += gradient_noise(p * scale, seed + octave) / scale;
+		scale *= 2.0;
+	}
+	return sum;
+}
+
+const MIN_TAPER_SCALE: f32 = 0.1; // assert: equal to `MIN_TAPER_SCALE` in `sdf.rs`
+
+const PI: f32 = 3.14159265358979323846;
+const OCEAN_ITERATIONS: u32 = 4u;
+
+// Get the phase of an ocean's `w`th wave at a point on the undisturbed surface, at the current time.
+fn wave_phase(offset: u32, w: u32, p: vec3<f32>) -> f32 {
+	let wave = parameters[offset + 1u + 2u * w]; // direction x, direction z, amplitude, wavelength
+	let speed = parameters[offset + 2u + 2u * w].x;
+	let wavenumber = 2.0 * PI / wave.w;
+	return wavenumber * (p.x * wave.x + p.z * wave.y - speed * uniforms.seconds);
+}
+
+// This must match `ocean_height` in `sdf.rs`.
+fn ocean_height(offset: u32, p: vec3<f32>) -> f32 {
+	let count = u32(parameters[offset].x);
+	let choppiness = parameters[offset].y;
+	var q = p;
+	for (var n = 0u; n < OCEAN_ITERATIONS; n++) {
+		var shift = vec3<f32>(0.0);
+		for (var w = 0u; w < count; w++) {
+			let wave = parameters[offset + 1u + 2u * w];
+			let direction = vec3<f32>(wave.x, 0.0, wave.y);
+			shift = shift + direction * (choppiness * wave.z * sin(wave_phase(offset, w, q)));
+		}
+		q = p + shift;
+	}
+	var height = 0.0;
+	for (var w = 0u; w < count; w++) {
+		height += parameters[offset + 1u + 2u * w].z * cos(wave_phase(offset, w, q));
+	}
+	return height;
+}
+
+// The point to evaluate the child of a node with one child at, for nodes other than `Repeat`.
+fn child_point(i: u32, p: vec3<f32>) -> vec3<f32> {
+	switch distances[i].tag {
+		case 6u {
+			let offset = distances[i].y;
+			let q = vec4<f32>(p, 1.0);
+			return vec3<f32>(dot(parameters[offset], q), dot(parameters[offset + 1u], q), dot(parameters[offset + 2u], q));
+		}
+		case 8u {
+			let normal: vec3<f32> = distances[i].v.xyz;
+			let offset: f32 = distances[i].v.w;
+			return p - normal * (2.0 * min(dot(p, normal) - offset, 0.0));
+		}
+		case 9u {
+			let axes = distances[i].y;
+			let flip = vec3<bool>((axes & 1u) != 0u, (axes & 2u) != 0u, (axes & 4u) != 0u);
+			return select(p, abs(p), flip);
+		}
+		case 10u {
+			let axis: vec3<f32> = distances[i].v.xyz;
+			let along: vec3<f32> = parameters[distances[i].y].xyz;
+			let angle = -distances[i].v.w * dot(p, along);
+			return p * cos(angle) + cross(axis, p) * sin(angle) + axis * (dot(axis, p) * (1.0 - cos(angle)));
+		}
+		case 11u {
+			let axis: vec3<f32> = distances[i].v.xyz;
+			let h = dot(p, axis);
+			let scale = max(1.0 + distances[i].v.w * h, MIN_TAPER_SCALE);
+			return axis * h + (p - axis * h) / scale;
+		}
+		case 12u {
+			let extent: vec3<f32> = distances[i].v.xyz;
+			return p - clamp(p, -extent, extent);
+		}
+		case 17u {
+			let axis: vec3<f32> = distances[i].v.xyz;
+			let angle = -distances[i].v.w;
+			return p * cos(angle) + cross(axis, p) * sin(angle) + axis * (dot(axis, p) * (1.0 - cos(angle)));
+		}
+		default { return p; }
+	}
+}
+
+// The point to evaluate a repeated child at, in one of the up to 8 cells nearest to `p`.
+// Bit `axis` of `cell` picks the neighboring cell along that axis instead of the cell containing `p`.
+// Returns `w = 0.0` if that isn't a distinct cell. This must match `repeat_points` in `sdf.rs`.
+fn repeat_point(i: u32, p: vec3<f32>, cell: u32) -> vec4<f32> {
+	let period = distances[i].v.xyz;
+	let counts = parameters[distances[i].y].xyz; // zero if infinite
+	var q = p;
+	for (var axis = 0u; axis < 3u; axis++) {
+		let s = period[axis];
+		let neighbor = ((cell >> axis) & 1u) == 1u;
+		if s == 0.0 {
+			if neighbor { return vec4<f32>(q, 0.0); }
+			continue;
+		}
+		let id = round(p[axis] / s);
+		var own = id;
+		var other = id + select(1.0, -1.0, p[axis] - s * id < 0.0);
+		if counts[axis] > 0.0 {
+			own = clamp(own, 0.0, counts[axis] - 1.0);
+			other = clamp(other, 0.0, counts[axis] - 1.0);
+		}
+		if !neighbor {
+			q[axis] = p[axis] - s * own;
+		} else if other != own {
+			q[axis] = p[axis] - s * other;
+		} else {
+			return vec4<f32>(q, 0.0);
+		}
+	}
+	return vec4<f32>(q, 1.0);
+}
+
 fn distance(p: vec3<f32>) -> DistanceResult {
-	var ds: array<f32, 100>; // assert: 100 <= arrayLength(distances)
-	var ms: array<u32, 100>; // assert: 100 <= arrayLength(distances)
-	for (var i: u32 = 0u; i < arrayLength(&distances); i++) {
+	var frames: array<Frame, MAX_DEPTH>;
+	var values: array<DistanceResult, MAX_DEPTH>; // the results of finished children
+	var f = 1u; // the number of frames
+	var v = 0u; // the number of values
+	frames[0] = Frame(arrayLength(&distances) - 1u, 0u, p);
+
+	while f > 0u {
+		let i = frames[f - 1u].node;
+		let phase = frames[f - 1u].phase;
+		let q = frames[f - 1u].point;
 		switch distances[i].tag {
 			case 0u {
 				let center: vec3<f32> = distances[i].v.xyz;
 				let radius: f32 = distances[i].v.w;
-				ds[i] = length(p - center) - radius;
-				ms[i] = distances[i].x;
+				values[v] = DistanceResult(length(q - center) - radius, distances[i].x);
+				v++;
+				f--;
 			}
 			case 1u {
 				let normal: vec3<f32> = distances[i].v.xyz;
 				let offset: f32 = distances[i].v.w;
-				ds[i] = dot(p, normal) - offset;
-				ms[i] = distances[i].x;
+				values[v] = DistanceResult(dot(q, normal) - offset, distances[i].x);
+				v++;
+				f--;
 			}
-			default {
-				let x = ds[distances[i].x];
-				let y = ds[distances[i].y];
-				switch distances[i].tag {
-					case 2u {
-						if x < y { ds[i] = x; ms[i] = ms[distances[i].x]; }
-						else     { ds[i] = y; ms[i] = ms[distances[i].y]; }
-					}
-					case 3u {
-						if x > y { ds[i] = x; ms[i] = ms[distances[i].x]; }
-						else     { ds[i] = y; ms[i] = ms[distances[i].y]; }
+			case 16u {
+				let local = q - distances[i].v.xyz;
+				let error = parameters[distances[i].y].z;
+				values[v] = DistanceResult(local.y - ocean_height(distances[i].y, local) - error, distances[i].x);
+				v++;
+				f--;
+			}
+			case 18u, 19u {
+				values[v] = DistanceResult(distances[i].v.x, distances[i].x);
+				v++;
+				f--;
+			}
+			case 6u, 8u, 9u, 10u, 11u, 12u, 17u {
+				if phase == 0u {
+					frames[f - 1u].phase = 1u;
+					frames[f] = Frame(distances[i].x, 0u, child_point(i, q));
+					f++;
+				} else {
+					if distances[i].tag == 6u {
+						values[v - 1u].distance *= distances[i].v.x; // the scale
 					}
-					case 4u {
-						ds[i] = max(min(x, y), -max(x, y));
-						// todo: material
+					f--;
+				}
+			}
+			case 13u, 14u, 15u {
+				if phase == 0u {
+					frames[f - 1u].phase = 1u;
+					frames[f] = Frame(distances[i].x, 0u, q);
+					f++;
+				} else {
+					let d = values[v - 1u].distance;
+					switch distances[i].tag {
+						case 13u { values[v - 1u].distance = d - distances[i].v.x; }
+						case 14u { values[v - 1u].distance = abs(d) - distances[i].v.x; }
+						case 15u {
+							let noise = fbm(q * distances[i].v.y, distances[i].y, u32(distances[i].v.z));
+							values[v - 1u].distance = d + distances[i].v.x * noise;
+						}
+						default {}
 					}
-					case 5u {
-						if -x > y { ds[i] = -x; ms[i] = ms[distances[i].x]; }
-						else      { ds[i] =  y; ms[i] = ms[distances[i].y]; }
+					f--;
+				}
+			}
+			case 7u {
+				// the phase is the next cell to try, and the first cell is always distinct
+				if phase >= 2u {
+					let b = values[v - 1u];
+					v--;
+					if b.distance < values[v - 1u].distance { values[v - 1u] = b; }
+				}
+				var cell = phase;
+				var point = vec4<f32>(0.0);
+				while cell < 8u {
+					point = repeat_point(i, q, cell);
+					if point.w > 0.0 { break; }
+					cell++;
+				}
+				if cell < 8u {
+					frames[f - 1u].phase = cell + 1u;
+					frames[f] = Frame(distances[i].x, 0u, point.xyz);
+					f++;
+				} else {
+					f--;
+				}
+			}
+			default {
+				if phase == 0u {
+					frames[f - 1u].phase = 1u;
+					frames[f] = Frame(distances[i].x, 0u, q);
+					f++;
+				} else if phase == 1u {
+					frames[f - 1u].phase = 2u;
+					frames[f] = Frame(distances[i].y, 0u, q);
+					f++;
+				} else {
+					let a = values[v - 2u];
+					let b = values[v - 1u];
+					let x = a.distance;
+					let y = b.distance;
+					v--;
+					switch distances[i].tag {
+						case 2u {
+							if x < y { values[v - 1u] = a; }
+							else     { values[v - 1u] = b; }
+						}
+						case 3u {
+							if x > y { values[v - 1u] = a; }
+							else     { values[v - 1u] = b; }
+						}
+						case 4u {
+							values[v - 1u].distance = max(min(x, y), -max(x, y));
+							// todo: material
+						}
+						case 5u {
+							if -x > y { values[v - 1u] = DistanceResult(-x, a.material); }
+							else      { values[v - 1u] = b; }
+						}
+						default {}
 					}
-					default {}
+					f--;
 				}
 			}
 		}
 	}
-	var out: DistanceResult;
-	out.distance = ds[arrayLength(&distances) - 1u];
-	out.material = ms[arrayLength(&distances) - 1u];
-	return out;
+	return values[0];
 }
 
 struct RaymarchResult {
@@ -126,7 +394,7 @@ fn raymarch(pos: vec3<f32>, dir: vec3<f32>) -> RaymarchResult {
 			return out;
 		}
 		out.steps += 1u;
-		accum += distanceResult.distance;
+		accum += distanceResult.distance / uniforms.lipschitz;
 	}
 
 	out.tag = 2u;
@@ -0,0 +1,179 @@
+//! A fixed-timestep game loop, which runs the simulation at the same rate regardless of the frame
+//! rate, so that it is deterministic, and interpolates between ticks for smooth rendering.
+
+use crate::*;
+use std::time::Duration;
+
+/// The number of simulation ticks per second.
+pub const TICK_RATE: u32 = 60;
+
+/// The most ticks that one frame will catch up on. After a longer stall, the simulation slows
+/// down instead of spending ever longer catching up.
+const MAX_TICKS_PER_FRAME: u32 = 8;
+
+/// Get the simulated time that passes in one tick, in seconds.
+pub fn tick_seconds() -> Fixed {
+    Fixed::ONE / Fixed::from_int(i32::try_from(TICK_RATE).expect("tick rate is small"))
+}
+
+/// A deterministic random number generator (splitmix64), which produces the same sequence on
+/// every machine for the same seed.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Rng(u64);
+
+impl Rng {
+    /// Start a sequence from `seed`.
+    #[must_use]
+    pub fn new(seed: u64) -> Rng {
+        Rng(seed)
+    }
+
+    /// Get the next number in the sequence.
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Get a number uniformly distributed in `[0, 1)`.
+    pub fn next_fixed(&mut self) -> Fixed {
+        Fixed::from_bits(i64::from((self.next_u64() >> 32) as u32))
+    }
+}
+
+/// State that can be blended between two ticks for rendering.
+pub trait Interpolate {
+    /// Blend from `self` at `t = 0` to `other` at `t = 1`.
+    #[must_use]
+    fn interpolate(&self, other: &Self, t: f32) -> Self;
+}
+
+impl Interpolate for Vec3 {
+    fn interpolate(&self, other: &Vec3, t: f32) -> Vec3 {
+        self.lerp(*other, t)
+    }
+}
+
+/// Something that advances in fixed ticks. It must only use deterministic math, like `Fixed`, so
+/// that every machine that steps it with the same inputs ends up in the same state.
+pub trait Simulation {
+    /// The inputs that one tick is stepped with.
+    type Input;
+    /// The part of the state that rendering needs.
+    type RenderState: Interpolate;
+
+    /// Advance by one tick.
+    fn step(&mut self, inputs: &Self::Input);
+
+    /// Get the part of the current state that rendering needs.
+    fn render_state(&self) -> Self::RenderState;
+
+    /// Feed the whole state into `hasher`, with `StableHasher::system` for each system, so that
+    /// desync dumps can say which one diverged.
+    fn hash_state(&self, hasher: &mut StableHasher);
+
+    /// Get a hash of the whole state, which is the same on every machine that has the same state.
+    /// Comparing checksums finds the first tick where two runs diverged.
+    fn checksum(&self) -> u64 {
+        let mut hasher = StableHasher::new();
+        self.hash_state(&mut hasher);
+        hasher.finish()
+    }
+}
+
+/// Turns wall-clock time into fixed ticks.
+#[derive(Clone, Debug, Default)]
+pub struct Clock {
+    accumulator: Duration,
+}
+
+impl Clock {
+    /// Add `elapsed` wall-clock time, and get the number of ticks that are now due.
+    pub fn advance(&mut self, elapsed: Duration) -> u32 {
+        let tick_duration = Duration::from_secs(1) / TICK_RATE;
+        self.accumulator += elapsed;
+        let mut ticks = 0;
+        while self.accumulator >= tick_duration {
+            if ticks == MAX_TICKS_PER_FRAME {
+                self.accumulator = Duration::ZERO;
+                break;
+            }
+            self.accumulator -= tick_duration;
+            ticks += 1;
+        }
+        ticks
+    }
+
+    /// Get how far it is from the last tick to the next one, from 0 to 1.
+    #[must_use]
+    pub fn alpha(&self) -> f32 {
+        self.accumulator.as_secs_f32() * TICK_RATE as f32
+    }
+}
+
+/// Drives a simulation from wall-clock time. The caller runs the ticks that `advance` says are
+/// due with `step`, so that it can supply and record inputs for each one.
+pub struct FixedLoop<S: Simulation> {
+    simulation: S,
+    tick: u64,
+    clock: Clock,
+    previous: S::RenderState,
+    current: S::RenderState,
+}
+
+impl<S: Simulation> FixedLoop<S> {
+    /// Start driving a simulation at tick zero.
+    pub fn new(simulation: S) -> FixedLoop<S> {
+        let current = simulation.render_state();
+        FixedLoop {
+            previous: simulation.render_state(),
+            current,
+            simulation,
+            tick: 0,
+            clock: Clock::default(),
+        }
+    }
+
+    /// Get the simulation.
+    pub fn simulation(&self) -> &S {
+        &self.simulation
+    }
+
+    /// Get the number of ticks that have run.
+    #[must_use]
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    /// Add `elapsed` wall-clock time, and get the number of ticks that are now due.
+    pub fn advance(&mut self, elapsed: Duration) -> u32 {
+        self.clock.advance(elapsed)
+    }
+
+    /// Run one tick with `inputs`.
+    pub fn step(&mut self, inputs: &S::Input) {
+        self.simulation.step(inputs);
+        self.previous = std::mem::replace(&mut self.current, self.simulation.render_state());
+        self.tick += 1;
+    }
+
+    /// Get how far it is from the last tick to the next one, from 0 to 1.
+    #[must_use]
+    pub fn alpha(&self) -> f32 {
+        self.clock.alpha()
+    }
+
+    /// Get the state to render, interpolated between the last two ticks. Rendering lags the
+    /// simulation by up to one tick, so that it never has to guess.
+    pub fn render_state(&self) -> S::RenderState {
+        self.previous.interpolate(&self.current, self.alpha())
+    }
+
+    /// Get the simulated time that `render_state` is at, in seconds.
+    #[must_use]
+    pub fn render_seconds(&self) -> f32 {
+        (self.tick.saturating_sub(1) as f32 + self.alpha()) / TICK_RATE as f32
+    }
+}
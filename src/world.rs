@@ -0,0 +1,505 @@
+//! The game's simulation: a ship for each player, the crew aboard them, and the cannonballs that
+//! they fire, stored as entities with components and updated by systems in a fixed order. Ships
+//! and cannonballs are rigid bodies, which collide with each other and the docks.
+
+use crate::*;
+
+/// How fast ships turn with the rudder over, in radians per second.
+const TURN_RATE: Fixed = Fixed::FRAC_PI_2;
+
+/// How fast ships change how fast they turn, in radians per second squared. Collisions can set
+/// them spinning faster than the rudder does, and this is how fast the keel slows them down.
+const TURN_ACCELERATION: Fixed = Fixed::PI;
+
+/// How fast ships speed up and slow down, in units per second squared.
+fn acceleration() -> Fixed {
+    Fixed::ONE / Fixed::from_int(2)
+}
+
+/// The fastest that ships sail, in units per second. They can go backwards at half of it.
+fn max_speed() -> Fixed {
+    Fixed::from_int(2)
+}
+
+/// The number of ticks between shots from the same ship.
+const RELOAD_TICKS: u32 = 30;
+
+/// How fast cannonballs leave the cannon, in units per second.
+fn muzzle_speed() -> Fixed {
+    Fixed::from_int(4)
+}
+
+/// The number of ticks that cannonballs last, if they don't hit a ship or fall into the sea.
+const PROJECTILE_TICKS: u32 = 300;
+
+/// A ship, which a player steers with left and right, speeds up and slows down with up and down,
+/// and fires with the action button.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Ship {
+    /// The player who sails it.
+    pub player: usize,
+    /// The number of ticks until it can fire again.
+    pub reload: u32,
+    /// The number of cannonballs that have hit it.
+    pub hits: u32,
+}
+
+impl StableHash for Ship {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        hasher.field("player", &self.player);
+        hasher.field("reload", &self.reload);
+        hasher.field("hits", &self.hits);
+    }
+}
+
+/// A member of a ship's crew, who stays at the same place on deck.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Crew {
+    /// The ship that they are aboard.
+    pub ship: Entity,
+    /// Where they stand, in the ship's frame.
+    pub station: FixedVec3,
+}
+
+impl StableHash for Crew {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        hasher.field("ship", &self.ship);
+        hasher.field("station", &self.station);
+    }
+}
+
+/// A cannonball, which flies until it hits a ship or falls into the sea, and bounces off the
+/// docks.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Projectile {
+    /// The number of ticks until it is removed anyway.
+    pub ticks: u32,
+}
+
+impl StableHash for Projectile {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        hasher.field("ticks", &self.ticks);
+    }
+}
+
+/// Every entity in the game, and their components.
+#[derive(Clone)]
+pub struct World {
+    /// The entities that exist.
+    pub entities: Entities,
+    /// Where entities are. Entities with a pose and a shape are rendered.
+    pub poses: Components<Pose>,
+    /// What entities look like, in their own frame.
+    pub shapes: Components<Sdf>,
+    /// The ships.
+    pub ships: Components<Ship>,
+    /// The crew aboard the ships.
+    pub crew: Components<Crew>,
+    /// The cannonballs in flight.
+    pub projectiles: Components<Projectile>,
+    /// How the ships and cannonballs move.
+    pub bodies: Components<Body>,
+    /// The surfaces of the ships and cannonballs.
+    pub colliders: Components<Collider>,
+    /// The rules of physics, and the docks.
+    pub physics: Physics,
+    /// The contacts of the last tick, in order of entity.
+    pub contacts: Vec<Contact>,
+}
+
+impl Default for World {
+    fn default() -> World {
+        World::new()
+    }
+}
+
+/// The systems, in the order that they run every tick.
+const SYSTEMS: [(&str, System<World, [InputFrame; PLAYERS]>); 6] = [
+    ("steer", steer),
+    ("fire", fire),
+    ("physics", physics),
+    ("hits", hits),
+    ("crew", crew),
+    ("projectiles", projectiles),
+];
+
+impl World {
+    /// Start a game with a ship for each player, side by side, each with a crew of two.
+    #[must_use]
+    pub fn new() -> World {
+        let mut world = World {
+            entities: Entities::new(),
+            poses: Components::new(),
+            shapes: Components::new(),
+            ships: Components::new(),
+            crew: Components::new(),
+            projectiles: Components::new(),
+            bodies: Components::new(),
+            colliders: Components::new(),
+            physics: Physics::new(terrain()),
+            contacts: vec![],
+        };
+        for (player, x) in (0..PLAYERS).zip((-1..).step_by(3)) {
+            // in front of the demo scene, facing away from the camera
+            let position = FixedVec3 {
+                x: Fixed::from_int(x),
+                y: Fixed::ZERO,
+                z: Fixed::from_int(-3),
+            };
+            world.spawn_ship(
+                player,
+                Pose {
+                    position,
+                    heading: Fixed::ZERO,
+                },
+            );
+        }
+        world
+    }
+
+    /// Spawn a ship for a player, with a crew of two, and get it.
+    pub fn spawn_ship(&mut self, player: usize, pose: Pose) -> Entity {
+        let ship = self.entities.spawn();
+        self.poses.insert(ship, pose);
+        let hull = Sdf::sphere(
+            0.25,
+            Material::Flat(Color {
+                r: 0.4,
+                g: 0.25,
+                b: 0.1,
+                a: 1.0,
+            }),
+        )
+        .elongate(Vec3 {
+            x: 0.0,
+            y: 0.0,
+            z: 0.5,
+        });
+        self.colliders
+            .insert(ship, Collider::new(&hull, Fixed::ONE, 48));
+        self.shapes.insert(ship, hull);
+        self.ships.insert(
+            ship,
+            Ship {
+                player,
+                reload: 0,
+                hits: 0,
+            },
+        );
+        // ships float, so only the keel and collisions change their height
+        let body = Body {
+            gravity: Fixed::ZERO,
+            restitution: Fixed::ONE / Fixed::from_int(5),
+            ..Body::new(Fixed::ONE, Fixed::ONE / Fixed::from_int(4))
+        };
+        self.bodies.insert(ship, body);
+
+        for z in [-1, 1] {
+            let station = FixedVec3 {
+                x: Fixed::ZERO,
+                y: Fixed::ONE / Fixed::from_int(3),
+                z: Fixed::from_int(z) / Fixed::from_int(4),
+            };
+            let sailor = self.entities.spawn();
+            self.poses.insert(sailor, pose.then(station));
+            self.shapes
+                .insert(sailor, Sdf::sphere(0.08, Material::Flat(Color::WHITE)));
+            self.crew.insert(sailor, Crew { ship, station });
+        }
+        ship
+    }
+
+    /// Spawn a cannonball, and get it.
+    pub fn spawn_projectile(&mut self, position: FixedVec3, velocity: FixedVec3) -> Entity {
+        let projectile = self.entities.spawn();
+        self.poses.insert(
+            projectile,
+            Pose {
+                position,
+                heading: Fixed::ZERO,
+            },
+        );
+        let ball = Sdf::sphere(0.06, Material::Flat(Color::BLACK));
+        self.colliders.insert(
+            projectile,
+            Collider::new(&ball, Fixed::ONE / Fixed::from_int(10), 14),
+        );
+        self.shapes.insert(projectile, ball);
+        self.projectiles.insert(
+            projectile,
+            Projectile {
+                ticks: PROJECTILE_TICKS,
+            },
+        );
+        // cannonballs are heavy for their size, but light next to a ship, and don't turn
+        let body = Body {
+            velocity,
+            restitution: Fixed::ONE / Fixed::from_int(2),
+            ..Body::new(Fixed::ONE / Fixed::from_int(20), Fixed::ZERO)
+        };
+        self.bodies.insert(projectile, body);
+        projectile
+    }
+
+    /// Remove an entity and all of its components. Returns false if it was already gone.
+    pub fn despawn(&mut self, entity: Entity) -> bool {
+        self.poses.remove(entity);
+        self.shapes.remove(entity);
+        self.ships.remove(entity);
+        self.crew.remove(entity);
+        self.projectiles.remove(entity);
+        self.bodies.remove(entity);
+        self.colliders.remove(entity);
+        self.entities.despawn(entity)
+    }
+
+    /// Compose the terrain, and the shapes of the entities in `frame` at their poses in it, into
+    /// one scene.
+    pub fn scene(&self, frame: &WorldFrame) -> Sdf {
+        let entities = frame.poses.iter().filter_map(|(entity, pose)| {
+            let shape = self.shapes.get(*entity)?.clone();
+            let rotation = Transform::rotation(Y, pose.heading);
+            Some(shape.transform(Transform::translation(pose.position).compose(rotation)))
+        });
+        std::iter::once(self.physics.terrain.clone())
+            .chain(entities)
+            .collect()
+    }
+}
+
+/// The poses of the entities that have shapes, for rendering, in order of entity.
+#[derive(Clone, Debug, Default)]
+pub struct WorldFrame {
+    poses: Vec<(Entity, RenderPose)>,
+}
+
+/// A `Pose` in floating point, for rendering.
+#[derive(Clone, Copy, Debug, Default)]
+struct RenderPose {
+    position: Vec3,
+    heading: f32,
+}
+
+/// Entities that only exist in `other` aren't blended, and ones that only exist in `self` are
+/// left out.
+impl Interpolate for WorldFrame {
+    fn interpolate(&self, other: &WorldFrame, t: f32) -> WorldFrame {
+        let poses = other
+            .poses
+            .iter()
+            .map(|&(entity, to)| {
+                let Ok(i) = self.poses.binary_search_by_key(&entity, |(e, _)| *e) else {
+                    return (entity, to);
+                };
+                let from = self.poses[i].1;
+                // turn the short way round
+                let turn = (to.heading - from.heading + std::f32::consts::PI)
+                    .rem_euclid(std::f32::consts::TAU)
+                    - std::f32::consts::PI;
+                let pose = RenderPose {
+                    position: from.position.lerp(to.position, t),
+                    heading: from.heading + turn * t,
+                };
+                (entity, pose)
+            })
+            .collect();
+        WorldFrame { poses }
+    }
+}
+
+impl Simulation for World {
+    type Input = [InputFrame; PLAYERS];
+    type RenderState = WorldFrame;
+
+    fn step(&mut self, inputs: &[InputFrame; PLAYERS]) {
+        for (_, system) in SYSTEMS {
+            system(self, *inputs);
+        }
+    }
+
+    fn render_state(&self) -> WorldFrame {
+        let poses = self
+            .poses
+            .iter()
+            .filter(|(entity, _)| self.shapes.get(*entity).is_some())
+            .map(|(entity, pose)| {
+                let pose = RenderPose {
+                    position: pose.position.to_f32(),
+                    heading: pose.heading.to_f32(),
+                };
+                (entity, pose)
+            })
+            .collect();
+        WorldFrame { poses }
+    }
+
+    fn hash_state(&self, hasher: &mut StableHasher) {
+        hasher.system("entities", &self.entities);
+        hasher.system("poses", &self.poses);
+        hasher.system("shapes", &self.shapes);
+        hasher.system("ships", &self.ships);
+        hasher.system("crew", &self.crew);
+        hasher.system("projectiles", &self.projectiles);
+        hasher.system("bodies", &self.bodies);
+        hasher.system("colliders", &self.colliders);
+        hasher.system("physics", &self.physics);
+        hasher.system("contacts", &self.contacts);
+    }
+}
+
+/// The docks and the rock that the ships can run into, in front of the demo scene.
+fn terrain() -> Sdf {
+    let wood = Material::Flat(Color {
+        r: 0.5,
+        g: 0.35,
+        b: 0.2,
+        a: 1.0,
+    });
+    let stone = Material::Flat(Color {
+        r: 0.45,
+        g: 0.45,
+        b: 0.45,
+        a: 1.0,
+    });
+    let dock = Sdf::sphere(0.1, wood)
+        .elongate(Vec3 {
+            x: 0.8,
+            y: 0.3,
+            z: 0.1,
+        })
+        .translate(Vec3 {
+            x: -1.0,
+            y: 0.0,
+            z: -1.6,
+        });
+    let rock = Sdf::sphere(0.5, stone).translate(Vec3 {
+        x: 2.6,
+        y: -0.1,
+        z: -1.6,
+    });
+    dock | rock
+}
+
+/// Turn the ships with the rudder, and speed them up or slow them down along their heading. The
+/// keel stops them sliding sideways, so they only keep the part of their velocity that is
+/// forwards, and it keeps them on the water.
+fn steer(world: &mut World, inputs: [InputFrame; PLAYERS]) {
+    let dt = tick_seconds();
+    for (entity, ship) in world.ships.iter() {
+        let input = inputs[ship.player];
+        let held = |button| if input.held(button) { dt } else { Fixed::ZERO };
+        let (Some(pose), Some(body)) = (world.poses.get(entity), world.bodies.get_mut(entity))
+        else {
+            continue;
+        };
+
+        let rudder = (held(Button::Right) - held(Button::Left)) / dt * TURN_RATE;
+        let turn = TURN_ACCELERATION * dt;
+        body.spin = Ord::clamp(rudder, body.spin - turn, body.spin + turn);
+
+        let forward = pose.forward();
+        let speed =
+            body.velocity.dot(forward) + (held(Button::Up) - held(Button::Down)) * acceleration();
+        let speed = Ord::clamp(speed, -max_speed() / Fixed::from_int(2), max_speed());
+        body.velocity = forward * speed;
+    }
+}
+
+/// Fire cannonballs from the ships whose players hold the action button, off to the side and
+/// upwards, along with the ship's own velocity.
+fn fire(world: &mut World, inputs: [InputFrame; PLAYERS]) {
+    let mut shots = vec![];
+    for (entity, ship) in world.ships.iter_mut() {
+        ship.reload = ship.reload.saturating_sub(1);
+        let (Some(pose), Some(body)) = (world.poses.get(entity), world.bodies.get(entity)) else {
+            continue;
+        };
+        if inputs[ship.player].held(Button::Action) && ship.reload == 0 {
+            ship.reload = RELOAD_TICKS;
+            let aim = FixedVec3 {
+                x: Fixed::ONE,
+                y: Fixed::ONE,
+                z: Fixed::ZERO,
+            };
+            let velocity = pose.rotate(aim.normalized()) * muzzle_speed() + body.velocity;
+            // clear of the hull, so that the cannonball doesn't hit its own ship
+            let muzzle = pose.then(FixedVec3 {
+                x: Fixed::from_int(2) / Fixed::from_int(5),
+                y: Fixed::ONE / Fixed::from_int(4),
+                z: Fixed::ZERO,
+            });
+            shots.push((muzzle.position, velocity));
+        }
+    }
+    for (position, velocity) in shots {
+        world.spawn_projectile(position, velocity);
+    }
+}
+
+/// Move the bodies, and collide them with each other and the docks.
+fn physics(world: &mut World, _inputs: [InputFrame; PLAYERS]) {
+    world.contacts = world.physics.step(
+        &mut world.poses,
+        &mut world.bodies,
+        &world.colliders,
+        &world.shapes,
+    );
+}
+
+/// Count the cannonballs that hit ships, and remove them.
+fn hits(world: &mut World, _inputs: [InputFrame; PLAYERS]) {
+    let mut sunk = vec![];
+    for contact in &world.contacts {
+        let Some(b) = contact.b else {
+            continue;
+        };
+        for (projectile, ship) in [(contact.a, b), (b, contact.a)] {
+            if world.projectiles.get(projectile).is_none() || sunk.contains(&projectile) {
+                continue;
+            }
+            if let Some(ship) = world.ships.get_mut(ship) {
+                ship.hits += 1;
+                sunk.push(projectile);
+            }
+        }
+    }
+    for entity in sunk {
+        world.despawn(entity);
+    }
+}
+
+/// Keep the crew at their stations, and remove the crew of ships that are gone.
+fn crew(world: &mut World, _inputs: [InputFrame; PLAYERS]) {
+    let mut lost = vec![];
+    for (entity, sailor) in world.crew.iter() {
+        match world.poses.get(sailor.ship).copied() {
+            Some(ship) => {
+                if let Some(pose) = world.poses.get_mut(entity) {
+                    *pose = ship.then(sailor.station);
+                }
+            }
+            None => lost.push(entity),
+        }
+    }
+    for entity in lost {
+        world.despawn(entity);
+    }
+}
+
+/// Remove the cannonballs that have fallen into the sea, or lasted too long.
+fn projectiles(world: &mut World, _inputs: [InputFrame; PLAYERS]) {
+    let mut splashed = vec![];
+    for (entity, projectile) in world.projectiles.iter_mut() {
+        projectile.ticks = projectile.ticks.saturating_sub(1);
+        let sunk = world
+            .poses
+            .get(entity)
+            .is_none_or(|pose| pose.position.y < Fixed::ZERO);
+        if sunk || projectile.ticks == 0 {
+            splashed.push(entity);
+        }
+    }
+    for entity in splashed {
+        world.despawn(entity);
+    }
+}